@@ -1,10 +1,31 @@
 //! MEGA.nz Account Generator CLI
 //!
 //! Usage:
-//!   meganz-account-generator --password <PASSWORD> [--name <NAME>] [--count <N>] [--output <FILE>] [--proxy <URL>] [--verbose]
+//!   meganz-account-generator (--password <PASSWORD> | --generate-password [--password-length <N>] [--password-alphabet <CHARS>]) [--name <NAME>] [--count <N>] [--output <FILE>] [--format <vault|jsonl|csv>] [--proxy <URL>] [--login-check] [--export-recovery-key] [--breached-passwords <FILE>] [--verbose] [--show-password]
+//!   meganz-account-generator vault <create|list|decrypt> <VAULT>
+//!   meganz-account-generator manual --email <EMAIL> [--name <NAME>] [--vault <VAULT>] [--proxy <URL>]
+//!   meganz-account-generator cancel <VAULT> (--email <EMAIL>... | --all) [--proxy <URL>] [--yes] [--verbose]
+//!   meganz-account-generator change-email --email <EMAIL> --new-email <EMAIL> [--vault <VAULT>] [--proxy <URL>]
+//!
+//! The vault passphrase is read from `MEGA_VAULT_PASSPHRASE` if set, otherwise it is prompted for. `manual` and
+//! `change-email` read the account password from `--vault`, or prompt for it.
+//!
+//! Exit codes:
+//!   0    every account was created
+//!   1    other failure
+//!   3    temporary failure (network, rate limit, service unavailable, timeout); retrying may succeed
+//!   4    rejected by MEGA or GuerrillaMail
+//!   5    confirmation email missing a valid link, or suspicious
+//!   6    local file or input error (vault, output file, pending registration, weak password)
+//!   130  interrupted
 
-use clap::Parser;
-use meganz_account_generator::AccountGenerator;
+use clap::{Parser, Subcommand, ValueEnum};
+use meganz_account_generator::export::{ExportFormat, ExportRecord};
+use meganz_account_generator::vault::{Vault, VaultEntry};
+use meganz_account_generator::{
+    AccountGenerator, CancellationToken, Error, ErrorKind, ExposeSecret, GeneratedAccount,
+    GenerationReport, PasswordGenerator, PasswordPolicy, ProgressEvent, ProxyConfig, SecretString,
+};
 use std::fs::OpenOptions;
 use std::io::Write;
 
@@ -12,10 +33,31 @@ use std::io::Write;
 #[derive(Parser, Debug)]
 #[command(name = "meganz-account-generator")]
 #[command(version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Password for the new account(s)
-    #[arg(short, long)]
-    password: String,
+    #[arg(short, long, required_unless_present = "generate_password")]
+    password: Option<String>,
+
+    /// Generate a unique random password for each account instead of using --password
+    #[arg(long, conflicts_with = "password")]
+    generate_password: bool,
+
+    /// Length of generated passwords
+    #[arg(long, default_value_t = PasswordGenerator::DEFAULT_LENGTH, requires = "generate_password")]
+    password_length: usize,
+
+    /// Characters generated passwords are drawn from
+    #[arg(
+        long,
+        value_name = "CHARS",
+        requires = "generate_password",
+        value_parser = clap::builder::NonEmptyStringValueParser::new()
+    )]
+    password_alphabet: Option<String>,
 
     /// Name for the account (random if not specified)
     #[arg(short, long)]
@@ -25,50 +67,291 @@ struct Args {
     #[arg(short, long, default_value = "1")]
     count: u32,
 
-    /// Output file to save credentials (appends to file)
+    /// Output file to save credentials to (created if missing, appended to otherwise)
     #[arg(short, long)]
     output: Option<String>,
 
-    /// Proxy URL (e.g., http://127.0.0.1:8080)
+    /// Format of the output file
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Vault, requires = "output")]
+    format: OutputFormat,
+
+    /// Proxy URL: http, https or socks5 (e.g., http://127.0.0.1:8080)
+    #[arg(long)]
+    proxy: Option<ProxyConfig>,
+
+    /// Log in to each account after confirmation and report its storage quota
+    #[arg(long)]
+    login_check: bool,
+
+    /// Export each account's recovery key; it is stored only in vault output
     #[arg(long)]
-    proxy: Option<String>,
+    export_recovery_key: bool,
+
+    /// Reject the password if it is listed in this file (one password per line)
+    #[arg(long, value_name = "FILE")]
+    breached_passwords: Option<String>,
 
     /// Show detailed per-account output
     #[arg(short, long)]
     verbose: bool,
+
+    /// Include the plaintext password and recovery key in verbose output
+    #[arg(long, requires = "verbose")]
+    show_password: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// Passphrase-encrypted vault
+    Vault,
+    /// JSON Lines, one plaintext record per line
+    Jsonl,
+    /// CSV with a header row, plaintext
+    Csv,
+}
+
+impl OutputFormat {
+    fn export_format(self) -> Option<ExportFormat> {
+        match self {
+            OutputFormat::Vault => None,
+            OutputFormat::Jsonl => Some(ExportFormat::JsonLines),
+            OutputFormat::Csv => Some(ExportFormat::Csv),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage an encrypted credential vault
+    #[command(subcommand)]
+    Vault(VaultCommand),
+    /// Register an address you control and paste the confirmation link when it arrives
+    Manual {
+        /// Email address to register
+        #[arg(short, long)]
+        email: String,
+
+        /// Name for the account (random if not specified)
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Vault to save the confirmed account to; a password stored there for the address is reused
+        #[arg(long)]
+        vault: Option<String>,
+
+        /// Proxy URL: http, https or socks5 (e.g., http://127.0.0.1:8080)
+        #[arg(long)]
+        proxy: Option<ProxyConfig>,
+    },
+    /// Permanently delete accounts stored in a vault, confirming through their temporary inboxes
+    Cancel {
+        /// Vault written with `--output`
+        path: String,
+
+        /// Account to delete; repeat for several
+        #[arg(short, long, required_unless_present = "all")]
+        email: Vec<String>,
+
+        /// Delete every account in the vault
+        #[arg(long, conflicts_with = "email")]
+        all: bool,
+
+        /// Proxy URL: http, https or socks5 (e.g., http://127.0.0.1:8080)
+        #[arg(long)]
+        proxy: Option<ProxyConfig>,
+
+        /// Do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Show live progress for each account
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Move an account to an address you control and paste the link MEGA sends there
+    ChangeEmail {
+        /// Current email address of the account
+        #[arg(short, long)]
+        email: String,
+
+        /// Address to move the account to
+        #[arg(long)]
+        new_email: String,
+
+        /// Vault written with `--output` that holds the account; the password is prompted for otherwise
+        #[arg(long)]
+        vault: Option<String>,
+
+        /// Proxy URL: http, https or socks5 (e.g., http://127.0.0.1:8080)
+        #[arg(long)]
+        proxy: Option<ProxyConfig>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum VaultCommand {
+    /// Create a new, empty vault
+    Create { path: String },
+    /// List the accounts stored in a vault without their passwords
+    List { path: String },
+    /// Print every account stored in a vault, including passwords
+    Decrypt {
+        path: String,
+
+        /// Print records as JSON Lines or CSV instead of text blocks
+        #[arg(short, long, value_enum)]
+        format: Option<OutputFormat>,
+    },
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
 
+    match args.command {
+        Some(Command::Vault(ref command)) => run_vault(command),
+        Some(Command::Manual {
+            ref email,
+            ref name,
+            ref vault,
+            ref proxy,
+        }) => run_manual(email, name.as_deref(), vault.as_deref(), proxy.as_ref()).await,
+        Some(Command::Cancel {
+            ref path,
+            ref email,
+            all,
+            ref proxy,
+            yes,
+            verbose,
+        }) => run_cancel(path, email, all, proxy.as_ref(), yes, verbose).await,
+        Some(Command::ChangeEmail {
+            ref email,
+            ref new_email,
+            ref vault,
+            ref proxy,
+        }) => run_change_email(email, new_email, vault.as_deref(), proxy.as_ref()).await,
+        None => run_generate(args).await,
+    }
+}
+
+async fn run_generate(args: Args) {
+    let password_generator = args.generate_password.then(|| {
+        let generator = PasswordGenerator::new().length(args.password_length);
+        match args.password_alphabet.as_deref() {
+            Some(alphabet) => generator.alphabet(alphabet),
+            None => generator,
+        }
+    });
+    let next_password = || match &password_generator {
+        Some(generator) => generator.generate(),
+        None => SecretString::from(
+            args.password
+                .clone()
+                .expect("password is required without a subcommand or --generate-password"),
+        ),
+    };
+
+    // A generated password that is neither saved nor printed cannot be recovered
+    if password_generator.is_some() && args.output.is_none() && !args.show_password {
+        eprintln!("--generate-password requires --output or --verbose --show-password");
+        std::process::exit(EXIT_LOCAL);
+    }
+
+    // Recovery keys are too sensitive for the plaintext formats
+    if args.export_recovery_key && args.output.is_some() && args.format != OutputFormat::Vault {
+        eprintln!("--export-recovery-key requires --format vault when --output is set");
+        std::process::exit(EXIT_LOCAL);
+    }
+
+    // Check the password (or the generator's configuration) once instead of failing every account in the run
+    let mut policy = PasswordPolicy::default();
+    if let Some(path) = args.breached_passwords.as_deref() {
+        policy = policy.breached_passwords_file(path).unwrap_or_else(|e| {
+            eprintln!("Failed to read {}: {}", path, e);
+            std::process::exit(error_exit_code(&e));
+        });
+    }
+    let checked = match &password_generator {
+        Some(generator) => policy.check_generator(generator),
+        None => policy.check(next_password().expose_secret()),
+    };
+    if let Err(weakness) = checked {
+        let e = Error::WeakPassword(weakness);
+        eprintln!("{}", e);
+        std::process::exit(error_exit_code(&e));
+    }
+    // Estimating each generated password would reject unlucky draws from a generator that passed the check
+    if password_generator.is_some() {
+        policy = PasswordPolicy::permissive();
+    }
+
     println!("🚀 MEGA.nz Account Generator");
     println!("Creating {} account(s)...", args.count);
 
-    let mut builder = AccountGenerator::builder();
-    if let Some(proxy_url) = args.proxy {
-        builder = builder.proxy(proxy_url);
+    // Unlock the vault up front so a wrong passphrase fails before any account is created
+    let mut vault = match (args.output.as_deref(), args.format) {
+        (Some(path), OutputFormat::Vault) => {
+            let passphrase = read_passphrase(!std::path::Path::new(path).exists());
+            Some(
+                Vault::open_or_create(path, &passphrase).unwrap_or_else(|e| {
+                    eprintln!("Failed to open vault {}: {}", path, e);
+                    std::process::exit(error_exit_code(&e));
+                }),
+            )
+        }
+        _ => None,
+    };
+
+    let mut builder = AccountGenerator::builder().password_policy(policy);
+    if let Some(proxy) = args.proxy {
+        builder = builder.proxy(proxy);
+    }
+    if args.login_check {
+        builder = builder.login_check(true);
+    }
+    if args.export_recovery_key {
+        builder = builder.export_recovery_key(true);
+    }
+    if args.verbose {
+        builder = builder.on_progress(print_progress);
     }
 
     let generator = match builder.build().await {
         Ok(g) => g,
         Err(e) => {
             eprintln!("Failed to initialize: {}", e);
-            std::process::exit(1);
+            std::process::exit(error_exit_code(&e));
         }
     };
 
+    // Ctrl-C stops the current account promptly and still cleans up its temporary inbox
+    let cancel = CancellationToken::new();
+    tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                eprintln!("\nInterrupted, cleaning up...");
+                cancel.cancel();
+            }
+        }
+    });
+
     let mut successful = 0;
+    let mut exit_code = 0;
 
     for i in 1..=args.count {
         if args.verbose {
             println!("\n[{}/{}] Creating account...", i, args.count);
         }
 
+        let password = next_password();
+        let password = password.expose_secret();
         let result = if let Some(name) = args.name.as_deref() {
-            generator.generate_with_name(&args.password, name).await
+            generator
+                .generate_with_name_cancellable(password, name, &cancel)
+                .await
         } else {
-            generator.generate(&args.password).await
+            generator.generate_cancellable(password, &cancel).await
         };
 
         match result {
@@ -77,29 +360,75 @@ async fn main() {
                 if args.verbose {
                     println!("Status: SUCCESS");
                     println!("Email: {}", account.email);
-                    println!("Password: {}", account.password);
+                    if args.show_password {
+                        println!("Password: {}", account.password.expose_secret());
+                    } else {
+                        println!("Password: [REDACTED]");
+                    }
                     println!("Name: {}", account.name);
+                    if let Some(session) = &account.session {
+                        println!(
+                            "Storage: {} of {} bytes used",
+                            session.storage_used, session.storage_total
+                        );
+                    }
+                    match &account.recovery_key {
+                        Some(key) if args.show_password => {
+                            println!("Recovery key: {}", key.expose_secret())
+                        }
+                        Some(_) => println!("Recovery key: [REDACTED]"),
+                        None => {}
+                    }
+                    print_report(&account.report);
                 } else {
                     println!("[{}/{}] OK {}", i, args.count, account.email);
                 }
+                for warning in &account.report.warnings {
+                    eprintln!("Warning: {}", warning);
+                }
 
                 // Save to file if specified
                 if let Some(ref output_path) = args.output {
-                    if let Err(e) = save_to_file(output_path, &account) {
+                    let saved = match (&mut vault, args.format.export_format()) {
+                        (Some(vault), _) => vault
+                            .append(&VaultEntry::from(&account))
+                            .map_err(|e| e.to_string()),
+                        (None, Some(format)) => {
+                            export_to_file(output_path, format, &account).map_err(|e| e.to_string())
+                        }
+                        (None, None) => unreachable!("vault output is opened before generation"),
+                    };
+                    if let Err(e) = saved {
                         eprintln!("Failed to save to file: {}", e);
                     } else if args.verbose {
                         println!("Saved to {}", output_path);
                     }
                 }
             }
+            Err(Error::Cancelled { stage, email }) => {
+                exit_code = EXIT_INTERRUPTED;
+                match email {
+                    Some(email) => eprintln!(
+                        "[{}/{}] CANCELLED during {} ({})",
+                        i, args.count, stage, email
+                    ),
+                    None => eprintln!("[{}/{}] CANCELLED during {}", i, args.count, stage),
+                }
+                break;
+            }
             Err(e) => {
+                exit_code = error_exit_code(&e);
                 if args.verbose {
                     eprintln!("[{}/{}] Status: FAILED", i, args.count);
+                    eprintln!("Reason: {}", e);
+                    eprintln!("Retryable: {}", if e.is_retryable() { "yes" } else { "no" });
                 } else {
                     eprintln!("[{}/{}] FAILED {}", i, args.count, e);
                 }
-                if args.verbose {
-                    eprintln!("Reason: {}", e);
+                if e.leaves_unconfirmed_account()
+                    && let Some(email) = e.email()
+                {
+                    eprintln!("Unconfirmed account left for {}", email);
                 }
             }
         }
@@ -109,24 +438,487 @@ async fn main() {
             if args.verbose {
                 println!("\nWaiting 30 seconds before next account...");
             }
-            tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = tokio::time::sleep(std::time::Duration::from_secs(30)) => {}
+            }
+        }
+    }
+
+    // Inboxes of failed or interrupted runs may not have been deleted; try once more
+    if !generator.leftover_inboxes().is_empty() {
+        for warning in generator.cleanup().await {
+            eprintln!("Warning: {}", warning);
         }
     }
 
     println!("Done: {}/{} successful", successful, args.count);
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
 }
 
-fn save_to_file(
+async fn run_manual(
+    email: &str,
+    name: Option<&str>,
+    vault: Option<&str>,
+    proxy: Option<&ProxyConfig>,
+) {
+    let mut vault = vault.map(|path| {
+        let passphrase = read_passphrase(!std::path::Path::new(path).exists());
+        Vault::open_or_create(path, &passphrase).unwrap_or_else(|e| {
+            eprintln!("Failed to open vault {}: {}", path, e);
+            std::process::exit(error_exit_code(&e));
+        })
+    });
+    let stored = vault.as_ref().and_then(|vault| {
+        let entries = vault.entries().unwrap_or_else(|e| {
+            eprintln!("Failed to read vault {}: {}", vault.path().display(), e);
+            std::process::exit(error_exit_code(&e));
+        });
+        entries.into_iter().find(|entry| entry.email == email)
+    });
+    let password = match &stored {
+        Some(entry) => entry.password.clone(),
+        None => read_account_password(true),
+    };
+    let password = password.expose_secret();
+
+    let mut builder = AccountGenerator::builder();
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(proxy.clone());
+    }
+    let generator = builder.build_manual();
+
+    let pending = match generator.register_existing(email, password, name).await {
+        Ok(pending) => pending,
+        Err(e) => {
+            eprintln!("Registration failed: {}", e);
+            std::process::exit(error_exit_code(&e));
+        }
+    };
+    println!("Registration submitted for {}", pending.email);
+    println!("Open the email from MEGA and paste the confirmation link (or key) below.");
+
+    loop {
+        let line = prompt_line("Confirmation link");
+        match generator.confirm_manually(&pending, password, &line).await {
+            Ok(account) => {
+                println!("Status: SUCCESS");
+                println!("Email: {}", account.email);
+                println!("Name: {}", account.name);
+                for warning in &account.report.warnings {
+                    eprintln!("Warning: {}", warning);
+                }
+                if let Some(vault) = &mut vault
+                    && stored.is_none()
+                {
+                    if let Err(e) = vault.append(&VaultEntry::from(&account)) {
+                        eprintln!("Failed to save to {}: {}", vault.path().display(), e);
+                        std::process::exit(error_exit_code(&e));
+                    }
+                    println!("Saved to: {}", vault.path().display());
+                }
+                return;
+            }
+            // Typos and stray pastes are worth another try; a rejection from MEGA is not
+            Err(e @ (Error::NoConfirmationLink | Error::InvalidConfirmationLink(_))) => {
+                eprintln!("{}, try again", e);
+            }
+            Err(e) => {
+                eprintln!("Verification failed: {}", e);
+                std::process::exit(error_exit_code(&e));
+            }
+        }
+    }
+}
+
+async fn run_change_email(
+    email: &str,
+    new_email: &str,
+    vault: Option<&str>,
+    proxy: Option<&ProxyConfig>,
+) {
+    // The vault and its entries are kept to record the new address once the change is confirmed
+    let mut vault = vault.map(|path| {
+        let vault = Vault::open(path, &read_passphrase(false)).unwrap_or_else(|e| {
+            eprintln!("Failed to open vault {}: {}", path, e);
+            std::process::exit(error_exit_code(&e));
+        });
+        let entries = vault.entries().unwrap_or_else(|e| {
+            eprintln!("Failed to read vault {}: {}", path, e);
+            std::process::exit(error_exit_code(&e));
+        });
+        (vault, entries)
+    });
+    let account = match &vault {
+        Some((vault, entries)) => {
+            let Some(entry) = entries.iter().find(|entry| entry.email == email) else {
+                eprintln!("{} is not in {}", email, vault.path().display());
+                std::process::exit(EXIT_LOCAL);
+            };
+            GeneratedAccount {
+                email: entry.email.clone(),
+                password: entry.password.clone(),
+                name: entry.name.clone(),
+                session: None,
+                recovery_key: entry.recovery_key.clone(),
+                report: GenerationReport::default(),
+            }
+        }
+        None => GeneratedAccount {
+            email: email.to_string(),
+            password: read_account_password(false),
+            name: String::new(),
+            session: None,
+            recovery_key: None,
+            report: GenerationReport::default(),
+        },
+    };
+
+    let mut builder = AccountGenerator::builder();
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(proxy.clone());
+    }
+    let generator = builder.build_manual();
+
+    if let Err(e) = generator.request_email_change(&account, new_email).await {
+        eprintln!("Email change request failed: {}", e);
+        std::process::exit(error_exit_code(&e));
+    }
+    println!("Email change requested for {}", account.email);
+    println!(
+        "Open the email from MEGA at {} and paste the link (or key) below.",
+        new_email
+    );
+
+    loop {
+        let line = prompt_line("Verification link");
+        match generator
+            .confirm_email_change(&account, new_email, &line)
+            .await
+        {
+            Ok(account) => {
+                println!("Status: SUCCESS");
+                println!("Email: {}", account.email);
+                if let Some((vault, entries)) = &mut vault {
+                    for entry in entries.iter_mut().filter(|entry| entry.email == email) {
+                        entry.email = account.email.clone();
+                    }
+                    if let Err(e) = vault.rewrite(entries) {
+                        eprintln!("Failed to update vault {}: {}", vault.path().display(), e);
+                        std::process::exit(error_exit_code(&e));
+                    }
+                    println!("Vault updated: {}", vault.path().display());
+                }
+                return;
+            }
+            Err(e @ (Error::NoConfirmationLink | Error::InvalidConfirmationLink(_))) => {
+                eprintln!("{}, try again", e);
+            }
+            Err(e) => {
+                eprintln!("Email change failed: {}", e);
+                std::process::exit(error_exit_code(&e));
+            }
+        }
+    }
+}
+
+/// Prompt for a line of input, exiting if stdin is closed or unreadable.
+fn prompt_line(prompt: &str) -> String {
+    print!("{}: ", prompt);
+    let _ = std::io::stdout().flush();
+
+    let mut line = String::new();
+    match std::io::stdin().read_line(&mut line) {
+        Ok(0) => {
+            eprintln!("No input entered");
+            std::process::exit(EXIT_FAILURE);
+        }
+        Ok(_) => line,
+        Err(e) => {
+            eprintln!("Failed to read input: {}", e);
+            std::process::exit(EXIT_FAILURE);
+        }
+    }
+}
+
+async fn run_cancel(
+    path: &str,
+    emails: &[String],
+    all: bool,
+    proxy: Option<&ProxyConfig>,
+    yes: bool,
+    verbose: bool,
+) {
+    let entries = Vault::open(path, &read_passphrase(false))
+        .and_then(|vault| vault.entries())
+        .unwrap_or_else(|e| {
+            eprintln!("Failed to read vault {}: {}", path, e);
+            std::process::exit(error_exit_code(&e));
+        });
+    if let Some(missing) = emails
+        .iter()
+        .find(|email| !entries.iter().any(|entry| &entry.email == *email))
+    {
+        eprintln!("{} is not in {}", missing, path);
+        std::process::exit(EXIT_LOCAL);
+    }
+    let accounts: Vec<GeneratedAccount> = entries
+        .into_iter()
+        .filter(|entry| all || emails.contains(&entry.email))
+        .map(|entry| GeneratedAccount {
+            email: entry.email,
+            password: entry.password,
+            name: entry.name,
+            session: None,
+            recovery_key: entry.recovery_key,
+            report: GenerationReport::default(),
+        })
+        .collect();
+
+    if !yes {
+        println!("This permanently deletes {} account(s):", accounts.len());
+        for account in &accounts {
+            println!("  {}", account.email);
+        }
+        if prompt_line("Type 'yes' to continue").trim() != "yes" {
+            eprintln!("Aborted");
+            std::process::exit(EXIT_FAILURE);
+        }
+    }
+
+    let mut builder = AccountGenerator::builder();
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(proxy.clone());
+    }
+    if verbose {
+        builder = builder.on_progress(print_progress);
+    }
+    let generator = match builder.build().await {
+        Ok(g) => g,
+        Err(e) => {
+            eprintln!("Failed to initialize: {}", e);
+            std::process::exit(error_exit_code(&e));
+        }
+    };
+
+    let mut exit_code = 0;
+    for (i, account) in accounts.iter().enumerate() {
+        match generator.cancel_account(account).await {
+            Ok(()) => println!("[{}/{}] DELETED {}", i + 1, accounts.len(), account.email),
+            Err(e) => {
+                exit_code = error_exit_code(&e);
+                eprintln!(
+                    "[{}/{}] FAILED {}: {}",
+                    i + 1,
+                    accounts.len(),
+                    account.email,
+                    e
+                );
+            }
+        }
+    }
+    // Deleted accounts stay listed in the vault
+    std::process::exit(exit_code);
+}
+
+fn print_report(report: &GenerationReport) {
+    let Some(started_at) = report.started_at else {
+        return;
+    };
+    let steps = [
+        ("Inbox created", report.inbox_created_at),
+        ("Registered", report.registered_at),
+        ("First MEGA email", report.first_mega_email_at),
+        ("Verified", report.verified_at),
+        ("Cleaned up", report.cleaned_up_at),
+    ];
+    for (label, at) in steps {
+        if let Some(elapsed) = at.and_then(|at| at.duration_since(started_at).ok()) {
+            println!("{}: +{:.1}s", label, elapsed.as_secs_f64());
+        }
+    }
+    println!("Polls: {}", report.polls);
+    match report.inbox_deleted {
+        Some(true) => println!("Inbox deleted: yes"),
+        Some(false) => println!("Inbox deleted: no"),
+        None => {}
+    }
+}
+
+fn print_progress(event: &ProgressEvent) {
+    match event {
+        ProgressEvent::InboxCreated { email } => println!("  Inbox created: {}", email),
+        ProgressEvent::RegistrationSubmitted { .. } => {
+            println!("  Registration submitted, waiting for confirmation email...")
+        }
+        ProgressEvent::Polled { attempt, messages } => {
+            println!("  Poll #{}: {} message(s)", attempt, messages)
+        }
+        ProgressEvent::MegaEmailSeen { from, subject } => {
+            println!("  MEGA email from {}: {}", from, subject)
+        }
+        ProgressEvent::RetryScheduled {
+            stage,
+            attempt,
+            delay,
+        } => println!(
+            "  Retrying {} in {:.1}s (retry #{})",
+            stage,
+            delay.as_secs_f64(),
+            attempt
+        ),
+        ProgressEvent::ConfirmKeyExtracted => println!("  Confirmation key extracted"),
+        ProgressEvent::Verified { .. } => println!("  Registration verified"),
+        ProgressEvent::LoggedIn { .. } => println!("  Login check passed"),
+        ProgressEvent::RecoveryKeyExported { .. } => println!("  Recovery key exported"),
+        ProgressEvent::CancellationRequested { .. } => {
+            println!("  Cancellation requested, waiting for cancellation email...")
+        }
+        ProgressEvent::AccountCancelled { .. } => println!("  Account deleted"),
+        ProgressEvent::EmailChangeRequested { new_email, .. } => {
+            println!(
+                "  Email change requested, waiting for email at {}...",
+                new_email
+            )
+        }
+        ProgressEvent::EmailChanged { email, .. } => println!("  Email changed to {}", email),
+        ProgressEvent::InboxDeleted { deleted, .. } => {
+            if *deleted {
+                println!("  Inbox deleted");
+            } else {
+                println!("  Inbox deletion failed");
+            }
+        }
+        _ => {}
+    }
+}
+
+fn run_vault(command: &VaultCommand) {
+    let result = match command {
+        VaultCommand::Create { path } => {
+            Vault::create(path, &read_passphrase(true)).map(|_| println!("Created {}", path))
+        }
+        VaultCommand::List { path } => Vault::open(path, &read_passphrase(false))
+            .and_then(|vault| vault.entries())
+            .map(|entries| {
+                for entry in entries {
+                    println!("{}\t{}", entry.email, entry.name);
+                }
+            }),
+        VaultCommand::Decrypt { path, format } => Vault::open(path, &read_passphrase(false))
+            .and_then(|vault| vault.entries())
+            .map(
+                |entries| match format.and_then(OutputFormat::export_format) {
+                    Some(format) => {
+                        let mut stdout = std::io::stdout().lock();
+                        let written = format.write_header(&mut stdout).and_then(|()| {
+                            entries.iter().try_for_each(|entry| {
+                                format.write_record(&mut stdout, ExportRecord::from(entry))
+                            })
+                        });
+                        if let Err(e) = written {
+                            eprintln!("Failed to write output: {}", e);
+                            std::process::exit(EXIT_FAILURE);
+                        }
+                    }
+                    None => {
+                        for entry in entries {
+                            println!("---");
+                            println!("Email: {}", entry.email);
+                            println!("Password: {}", entry.password.expose_secret());
+                            println!("Name: {}", entry.name);
+                            if let Some(key) = &entry.recovery_key {
+                                println!("Recovery key: {}", key.expose_secret());
+                            }
+                        }
+                    }
+                },
+            ),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(error_exit_code(&e));
+    }
+}
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_TEMPORARY: i32 = 3;
+const EXIT_REJECTED: i32 = 4;
+const EXIT_INVALID_CONFIRMATION: i32 = 5;
+const EXIT_LOCAL: i32 = 6;
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// Pick the process exit code for a failed operation (see the module docs).
+fn error_exit_code(e: &Error) -> i32 {
+    match e.kind() {
+        _ if e.is_retryable() => EXIT_TEMPORARY,
+        ErrorKind::Rejected => EXIT_REJECTED,
+        ErrorKind::InvalidConfirmation => EXIT_INVALID_CONFIRMATION,
+        ErrorKind::Local => EXIT_LOCAL,
+        ErrorKind::Cancelled => EXIT_INTERRUPTED,
+        _ => EXIT_FAILURE,
+    }
+}
+
+/// Append `account` to a plaintext export file, writing the format header first if the file is new.
+fn export_to_file(
     path: &str,
-    account: &meganz_account_generator::GeneratedAccount,
+    format: ExportFormat,
+    account: &GeneratedAccount,
 ) -> std::io::Result<()> {
     let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if file.metadata()?.len() == 0 {
+        format.write_header(&mut file)?;
+    }
+    format.write_record(&mut file, ExportRecord::from(account))?;
+    file.flush()
+}
+
+/// Prompt for an account password on the terminal.
+///
+/// When `confirm` is set (registering a new account), the password is asked for twice.
+fn read_account_password(confirm: bool) -> SecretString {
+    let prompt = |label: &str| {
+        rpassword::prompt_password(label).unwrap_or_else(|e| {
+            eprintln!("Failed to read password: {}", e);
+            std::process::exit(EXIT_FAILURE);
+        })
+    };
 
-    writeln!(file, "---")?;
-    writeln!(file, "Email: {}", account.email)?;
-    writeln!(file, "Password: {}", account.password)?;
-    writeln!(file, "Name: {}", account.name)?;
-    writeln!(file)?;
+    let password = prompt("Account password: ");
+    if confirm && prompt("Confirm password: ") != password {
+        eprintln!("Passwords do not match");
+        std::process::exit(EXIT_FAILURE);
+    }
+    SecretString::from(password)
+}
 
-    Ok(())
+/// Read the vault passphrase from `MEGA_VAULT_PASSPHRASE` or the terminal.
+///
+/// When `confirm` is set (creating a new vault), an interactively entered passphrase is asked for twice.
+fn read_passphrase(confirm: bool) -> SecretString {
+    if let Ok(passphrase) = std::env::var("MEGA_VAULT_PASSPHRASE") {
+        return SecretString::from(passphrase);
+    }
+
+    let prompt = |label: &str| {
+        rpassword::prompt_password(label).unwrap_or_else(|e| {
+            eprintln!("Failed to read passphrase: {}", e);
+            std::process::exit(EXIT_FAILURE);
+        })
+    };
+
+    let passphrase = prompt("Vault passphrase: ");
+    if passphrase.is_empty() {
+        eprintln!("Vault passphrase must not be empty");
+        std::process::exit(EXIT_FAILURE);
+    }
+    if confirm && prompt("Confirm passphrase: ") != passphrase {
+        eprintln!("Passphrases do not match");
+        std::process::exit(EXIT_FAILURE);
+    }
+    SecretString::from(passphrase)
 }
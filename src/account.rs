@@ -1,28 +1,129 @@
+use crate::errors::Warning;
+use secrecy::SecretString;
+use std::time::{Duration, SystemTime};
+
 /// Credentials returned after successful account generation and confirmation.
 ///
-/// The `password` field is the same plaintext value passed to
+/// The `password` field holds the same value passed to
 /// [`crate::AccountGenerator::generate`] or
 /// [`crate::AccountGenerator::generate_with_name`].
 ///
 /// # Security
 ///
-/// This type intentionally stores and displays the plaintext password. Treat it as sensitive data.
+/// The password is stored as a [`SecretString`]: it is zeroized on drop and redacted by the `Debug` and
+/// `Display` implementations. Read it explicitly with [`secrecy::ExposeSecret::expose_secret`].
+///
+/// With the `serde` feature enabled, this type implements `Serialize` and `Deserialize`. Like
+/// [`SecretString`] itself, secrets can be loaded but are never saved: serialization omits the password, the
+/// session and the recovery key. Deserialization requires the password, and the session token when a session
+/// is present, so serialized output does not load back as an account with an empty password; a missing
+/// recovery key loads as `None`. Use [`crate::vault`] to store credentials, or [`crate::export`] to write them
+/// out in plaintext deliberately.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GeneratedAccount {
-    /// Temporary email address used for registration.
+    /// Email address of the account: the temporary address used for registration, unless it was moved with
+    /// [`crate::AccountGenerator::change_email`].
     pub email: String,
     /// Account password provided by the caller.
-    pub password: String,
+    #[cfg_attr(feature = "serde", serde(skip_serializing))]
+    pub password: SecretString,
     /// Account display name used during signup.
     pub name: String,
+    /// Session from the post-confirmation login, if [`crate::AccountGeneratorBuilder::login_check`] is
+    /// enabled.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub session: Option<AccountSession>,
+    /// Recovery key (the base64url-encoded master key), if
+    /// [`crate::AccountGeneratorBuilder::export_recovery_key`] is enabled.
+    ///
+    /// It resets the password of the account without losing its files, so store it like the password.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing))]
+    pub recovery_key: Option<SecretString>,
+    /// Timings of the run that produced the account.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub report: GenerationReport,
+}
+
+/// Timings and inbox statistics of one run of [`crate::AccountGenerator`].
+///
+/// Each timestamp is `None` when the run did not go through that step: accounts from
+/// [`crate::AccountGenerator::resume`] have no inbox creation or registration time, and accounts confirmed
+/// with [`crate::AccountGenerator::confirm_manually`] were never polled or cleaned up.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenerationReport {
+    /// When the call that produced the account started.
+    pub started_at: Option<SystemTime>,
+    /// When the temporary inbox was created.
+    pub inbox_created_at: Option<SystemTime>,
+    /// When MEGA accepted the registration.
+    pub registered_at: Option<SystemTime>,
+    /// When the first likely MEGA email was seen in the inbox.
+    pub first_mega_email_at: Option<SystemTime>,
+    /// When MEGA confirmed the registration.
+    pub verified_at: Option<SystemTime>,
+    /// When deletion of the temporary inbox finished.
+    pub cleaned_up_at: Option<SystemTime>,
+    /// Number of times the inbox was checked for new messages.
+    pub polls: u32,
+    /// Whether the mail provider reported the temporary inbox as deleted, or `None` if deletion was not
+    /// attempted.
+    pub inbox_deleted: Option<bool>,
+    /// Problems that did not fail the run, such as an inbox that could not be deleted.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub warnings: Vec<Warning>,
+}
+
+impl GenerationReport {
+    /// Time from the start of the call to the last recorded step.
+    pub fn duration(&self) -> Option<Duration> {
+        let end = [
+            self.cleaned_up_at,
+            self.verified_at,
+            self.first_mega_email_at,
+            self.registered_at,
+            self.inbox_created_at,
+        ]
+        .into_iter()
+        .flatten()
+        .max()?;
+        end.duration_since(self.started_at?).ok()
+    }
+}
+
+/// A logged-in MEGA session for a newly confirmed account, with basic account information.
+///
+/// # Security
+///
+/// `session` grants full access to the account without the password. It is redacted by `Debug`; with the
+/// `serde` feature enabled, serialization omits it and deserialization requires it.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccountSession {
+    /// Session in MEGA SDK format, as produced by `megalib::Session::dump_session`.
+    ///
+    /// Write it to a file and restore it with `megalib::Session::load`.
+    #[cfg_attr(feature = "serde", serde(skip_serializing))]
+    pub session: SecretString,
+    /// Total storage quota in bytes.
+    pub storage_total: u64,
+    /// Used storage in bytes.
+    pub storage_used: u64,
 }
 
 impl std::fmt::Display for GeneratedAccount {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Email: {}\nPassword: {}\nName: {}",
-            self.email, self.password, self.name
+            "Email: {}\nPassword: [REDACTED]\nName: {}",
+            self.email, self.name
         )
     }
 }
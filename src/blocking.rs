@@ -0,0 +1,280 @@
+//! Blocking wrapper around [`AccountGenerator`] for synchronous code.
+//!
+//! Enabled with the `blocking` cargo feature. [`BlockingAccountGenerator`] owns a Tokio runtime and runs each
+//! call of the wrapped generator to completion on it, so callers need no runtime of their own:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use meganz_account_generator::blocking::BlockingAccountGenerator;
+//!
+//! fn main() -> meganz_account_generator::Result<()> {
+//!     let generator = BlockingAccountGenerator::builder()
+//!         .timeout(Duration::from_secs(180))
+//!         .build_blocking()?;
+//!
+//!     let account = generator.generate("S3cure-Password!")?;
+//!     println!("Created account: {}", account.email);
+//!     Ok(())
+//! }
+//! ```
+//!
+//! The methods may also be called from inside an async runtime. Blocking there would panic, so the call is
+//! driven on a separate thread instead; the calling thread still blocks until it finishes, which stalls other
+//! tasks on a single-threaded runtime. Async code should use [`AccountGenerator`] directly.
+
+use crate::account::GeneratedAccount;
+use crate::errors::{Result, Warning};
+use crate::generator::{AccountGenerator, AccountGeneratorBuilder};
+use crate::mail::MailProvider;
+use crate::pending::PendingRegistration;
+use crate::registrar::{MegaRegistrar, Registrar};
+use guerrillamail_client::Client as MailClient;
+use std::future::Future;
+use tokio::runtime::{Builder, Handle, Runtime};
+use tokio_util::sync::CancellationToken;
+
+/// Synchronous version of [`AccountGenerator`].
+///
+/// Every method blocks until the corresponding [`AccountGenerator`] method completes and returns the same
+/// result, including the same [`crate::Error`] variants. Cancellation tokens can be triggered from another
+/// thread.
+pub struct BlockingAccountGenerator<M = MailClient, R = MegaRegistrar> {
+    inner: AccountGenerator<M, R>,
+    /// Always `Some` until dropped.
+    runtime: Option<Runtime>,
+}
+
+impl BlockingAccountGenerator {
+    /// Create a builder. Finish it with [`AccountGeneratorBuilder::build_blocking`].
+    pub fn builder() -> AccountGeneratorBuilder {
+        AccountGenerator::builder()
+    }
+
+    /// Create a generator with default settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::Io`] if the runtime cannot be started and [`crate::Error::Mail`] if the
+    /// GuerrillaMail client cannot be initialized.
+    pub fn new() -> Result<Self> {
+        Self::builder().build_blocking()
+    }
+}
+
+impl<M, R> BlockingAccountGenerator<M, R> {
+    /// Wrap an existing async generator, such as one from [`AccountGeneratorBuilder::build_with`] or
+    /// [`AccountGeneratorBuilder::build_manual`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::Io`] if the runtime cannot be started.
+    pub fn from_async(generator: AccountGenerator<M, R>) -> Result<Self> {
+        Ok(Self {
+            inner: generator,
+            runtime: Some(new_runtime()?),
+        })
+    }
+
+    /// The wrapped async generator.
+    pub fn get_ref(&self) -> &AccountGenerator<M, R> {
+        &self.inner
+    }
+
+    /// Run `fut` to completion on the internal runtime.
+    fn block_on<F>(&self, fut: F) -> F::Output
+    where
+        F: Future + Send,
+        F::Output: Send,
+    {
+        let runtime = self
+            .runtime
+            .as_ref()
+            .expect("runtime is only taken on drop");
+        block_on(runtime, fut)
+    }
+}
+
+impl<M, R> BlockingAccountGenerator<M, R>
+where
+    M: MailProvider + Sync,
+    R: Registrar + Sync,
+{
+    /// See [`AccountGenerator::generate`].
+    pub fn generate(&self, password: &str) -> Result<GeneratedAccount> {
+        self.block_on(self.inner.generate(password))
+    }
+
+    /// See [`AccountGenerator::generate_cancellable`].
+    pub fn generate_cancellable(
+        &self,
+        password: &str,
+        cancel: &CancellationToken,
+    ) -> Result<GeneratedAccount> {
+        self.block_on(self.inner.generate_cancellable(password, cancel))
+    }
+
+    /// See [`AccountGenerator::generate_with_name`].
+    pub fn generate_with_name(&self, password: &str, name: &str) -> Result<GeneratedAccount> {
+        self.block_on(self.inner.generate_with_name(password, name))
+    }
+
+    /// See [`AccountGenerator::generate_with_name_cancellable`].
+    pub fn generate_with_name_cancellable(
+        &self,
+        password: &str,
+        name: &str,
+        cancel: &CancellationToken,
+    ) -> Result<GeneratedAccount> {
+        self.block_on(
+            self.inner
+                .generate_with_name_cancellable(password, name, cancel),
+        )
+    }
+
+    /// See [`AccountGenerator::start_registration`].
+    pub fn start_registration(
+        &self,
+        password: &str,
+        name: Option<&str>,
+    ) -> Result<PendingRegistration> {
+        self.block_on(self.inner.start_registration(password, name))
+    }
+
+    /// See [`AccountGenerator::resume`].
+    pub fn resume(
+        &self,
+        pending: &PendingRegistration,
+        password: &str,
+    ) -> Result<GeneratedAccount> {
+        self.block_on(self.inner.resume(pending, password))
+    }
+
+    /// See [`AccountGenerator::resume_cancellable`].
+    pub fn resume_cancellable(
+        &self,
+        pending: &PendingRegistration,
+        password: &str,
+        cancel: &CancellationToken,
+    ) -> Result<GeneratedAccount> {
+        self.block_on(self.inner.resume_cancellable(pending, password, cancel))
+    }
+
+    /// See [`AccountGenerator::leftover_inboxes`].
+    pub fn leftover_inboxes(&self) -> Vec<String> {
+        self.inner.leftover_inboxes()
+    }
+
+    /// See [`AccountGenerator::cleanup`].
+    pub fn cleanup(&self) -> Vec<Warning> {
+        self.block_on(self.inner.cleanup())
+    }
+
+    /// See [`AccountGenerator::change_email`].
+    pub fn change_email<N: MailProvider + Sync>(
+        &self,
+        account: &GeneratedAccount,
+        new_email: &str,
+        mail: &N,
+    ) -> Result<GeneratedAccount> {
+        self.block_on(self.inner.change_email(account, new_email, mail))
+    }
+
+    /// See [`AccountGenerator::cancel_account`].
+    pub fn cancel_account(&self, account: &GeneratedAccount) -> Result<()> {
+        self.block_on(self.inner.cancel_account(account))
+    }
+}
+
+impl<M, R> BlockingAccountGenerator<M, R>
+where
+    M: Sync,
+    R: Registrar + Sync,
+{
+    /// See [`AccountGenerator::register_existing`].
+    pub fn register_existing(
+        &self,
+        email: &str,
+        password: &str,
+        name: Option<&str>,
+    ) -> Result<PendingRegistration> {
+        self.block_on(self.inner.register_existing(email, password, name))
+    }
+
+    /// See [`AccountGenerator::confirm_manually`].
+    pub fn confirm_manually(
+        &self,
+        pending: &PendingRegistration,
+        password: &str,
+        confirmation: &str,
+    ) -> Result<GeneratedAccount> {
+        self.block_on(self.inner.confirm_manually(pending, password, confirmation))
+    }
+
+    /// See [`AccountGenerator::request_email_change`].
+    pub fn request_email_change(&self, account: &GeneratedAccount, new_email: &str) -> Result<()> {
+        self.block_on(self.inner.request_email_change(account, new_email))
+    }
+
+    /// See [`AccountGenerator::confirm_email_change`].
+    pub fn confirm_email_change(
+        &self,
+        account: &GeneratedAccount,
+        new_email: &str,
+        link: &str,
+    ) -> Result<GeneratedAccount> {
+        self.block_on(self.inner.confirm_email_change(account, new_email, link))
+    }
+}
+
+impl<M, R> Drop for BlockingAccountGenerator<M, R> {
+    fn drop(&mut self) {
+        // Unlike a plain drop, this does not panic when the generator is dropped inside an async runtime
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl AccountGeneratorBuilder {
+    /// Build a [`BlockingAccountGenerator`] using GuerrillaMail and MEGA.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::Io`] if the runtime cannot be started and [`crate::Error::Mail`] if the
+    /// GuerrillaMail client cannot be initialized.
+    pub fn build_blocking(self) -> Result<BlockingAccountGenerator> {
+        let runtime = new_runtime()?;
+        match block_on(&runtime, self.build()) {
+            Ok(inner) => Ok(BlockingAccountGenerator {
+                inner,
+                runtime: Some(runtime),
+            }),
+            Err(e) => {
+                runtime.shutdown_background();
+                Err(e)
+            }
+        }
+    }
+}
+
+fn new_runtime() -> Result<Runtime> {
+    Ok(Builder::new_current_thread().enable_all().build()?)
+}
+
+/// Run `fut` to completion on `runtime`, from any thread.
+fn block_on<F>(runtime: &Runtime, fut: F) -> F::Output
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    if Handle::try_current().is_err() {
+        return runtime.block_on(fut);
+    }
+    // `Runtime::block_on` panics on a thread that is already driving a runtime, so use one that is not
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| runtime.block_on(fut))
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
@@ -0,0 +1,361 @@
+use crate::errors::SuspicionReason;
+use crate::mail::InboxMessage;
+use crate::mime::text_parts;
+use url::Url;
+
+/// Domains MEGA sends account emails from.
+const TRUSTED_SENDER_DOMAINS: &[&str] = &["mega.nz", "mega.io", "mega.co.nz"];
+
+/// Hosts that serve MEGA confirmation links.
+const TRUSTED_LINK_HOSTS: &[&str] = &[
+    "mega.nz",
+    "www.mega.nz",
+    "mega.io",
+    "www.mega.io",
+    "mega.co.nz",
+];
+
+/// Whether a message presents itself as coming from MEGA.
+///
+/// This is the loose check used to pick candidate messages out of the inbox. Candidates must still pass
+/// [`is_trusted_sender`] before their body is looked at.
+pub(crate) fn looks_like_mega(msg: &InboxMessage) -> bool {
+    msg.from.to_ascii_lowercase().contains("mega") || msg.subject.contains("MEGA")
+}
+
+/// Whether `from` is an address at one of MEGA's sending domains.
+///
+/// Accepts a bare address or a `Display Name <address>` pair. The domain must match exactly; lookalikes
+/// such as `mega.nz.example.com` or `notmega.nz` are rejected.
+pub(crate) fn is_trusted_sender(from: &str) -> bool {
+    let from = from.trim();
+    let address = match (from.rfind('<'), from.rfind('>')) {
+        (Some(start), Some(end)) if start < end && from[end + 1..].trim().is_empty() => {
+            &from[start + 1..end]
+        }
+        (None, None) => from,
+        _ => return false,
+    };
+
+    let Some((local, domain)) = address.trim().split_once('@') else {
+        return false;
+    };
+    if local.is_empty() || domain.contains('@') || !address.chars().all(|c| c.is_ascii_graphic()) {
+        return false;
+    }
+
+    let domain = domain.to_ascii_lowercase();
+    TRUSTED_SENDER_DOMAINS.contains(&domain.as_str())
+}
+
+/// Kind of action link MEGA sends by email.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LinkKind {
+    /// Signup confirmation: `#confirm<KEY>`.
+    Signup,
+    /// Confirmation of a new account email address: `#verify<KEY>`.
+    EmailChange,
+    /// Account cancellation: `#cancel<KEY>`.
+    Cancellation,
+}
+
+impl LinkKind {
+    /// Prefix that introduces the key in the link's fragment or path.
+    fn action(self) -> &'static str {
+        match self {
+            LinkKind::Signup => "confirm",
+            LinkKind::EmailChange => "verify",
+            LinkKind::Cancellation => "cancel",
+        }
+    }
+}
+
+/// How many levels of tracking redirects are unwrapped when looking for a confirmation link.
+const MAX_REDIRECT_DEPTH: usize = 3;
+
+/// Extract the key of a `kind` action link from a MEGA email body.
+///
+/// The body is first decoded (see [`crate::mime::text_parts`]), so quoted-printable soft line breaks and
+/// full MIME messages are handled. In each decoded part, the `href` of every HTML anchor and every
+/// whitespace-, quote- or tag-delimited URL in the text are parsed after HTML entity decoding. URLs whose
+/// path or fragment starts with the action for `kind` (`confirm` for signup) followed by a key are treated
+/// as action links,
+/// and links that carry another URL in a query parameter (as tracking redirects do) are unwrapped first.
+/// MEGA signup confirmation links look like:
+/// - `https://mega.nz/#confirm<KEY>`
+/// - `https://mega.nz/confirm<KEY>`
+/// - `https://mega.io/confirm#<KEY>`
+///
+/// Returns `Ok(None)` when the body contains no such link, and an error when one is present but is not an
+/// `https` link to a MEGA host or carries a malformed key.
+pub(crate) fn extract_link_key(
+    body: &str,
+    kind: LinkKind,
+) -> Result<Option<String>, SuspicionReason> {
+    let mut found = None;
+
+    for part in text_parts(body) {
+        for url in candidate_urls(&part) {
+            let Some(url) = unwrap_redirects(url, kind, 0) else {
+                continue;
+            };
+            let raw_key = action_suffix(&url, kind).unwrap_or_default();
+
+            let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+            if !TRUSTED_LINK_HOSTS.contains(&host.as_str()) {
+                return Err(SuspicionReason::UntrustedLinkHost(host));
+            }
+            if url.scheme() != "https" {
+                return Err(SuspicionReason::InsecureLink);
+            }
+            if raw_key.is_empty() || !raw_key.chars().all(is_key_char) {
+                return Err(SuspicionReason::MalformedKey);
+            }
+
+            match &found {
+                None => found = Some(raw_key.to_string()),
+                Some(existing) if existing == raw_key => {}
+                Some(_) => return Err(SuspicionReason::ConflictingKeys),
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+/// Parse every anchor `href` and every delimited `http(s)` URL in a decoded body part.
+fn candidate_urls(text: &str) -> Vec<Url> {
+    let decoded = decode_entities(text);
+    let tokens = decoded
+        .split(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '<' | '>'))
+        .map(|token| token.trim_end_matches(['.', ',', ';', ':', '!', '?', ')']))
+        .map(str::to_string);
+
+    anchor_hrefs(text)
+        .into_iter()
+        .chain(tokens)
+        .filter_map(|candidate| parse_http_url(&candidate))
+        .collect()
+}
+
+fn parse_http_url(candidate: &str) -> Option<Url> {
+    let candidate = candidate.trim();
+    let lower = candidate.get(..8).unwrap_or(candidate).to_ascii_lowercase();
+    if !(lower.starts_with("https://") || lower.starts_with("http://")) {
+        return None;
+    }
+    Url::parse(candidate).ok()
+}
+
+/// The `kind` action link `url` points to, either directly or through nested redirect query parameters.
+fn unwrap_redirects(url: Url, kind: LinkKind, depth: usize) -> Option<Url> {
+    if action_suffix(&url, kind).is_some() {
+        return Some(url);
+    }
+    if depth >= MAX_REDIRECT_DEPTH {
+        return None;
+    }
+    url.query_pairs()
+        .filter_map(|(_, value)| parse_http_url(&value))
+        .find_map(|target| unwrap_redirects(target, kind, depth + 1))
+}
+
+/// The text following the action for `kind` in the fragment or path of an action link.
+///
+/// The key must follow exactly `#confirm`, `/confirm` or `/confirm#` (for signup). For `/confirm#<KEY>` style
+/// links the key is the whole fragment. Pages whose name merely starts with the action, such as
+/// `/confirmation-help` or `#cancellation-policy`, are not action links, and neither is a path that carries
+/// anything but key characters after the action.
+fn action_suffix(url: &Url, kind: LinkKind) -> Option<&str> {
+    let action = kind.action();
+    if let Some(key) = url
+        .fragment()
+        .and_then(|fragment| fragment.strip_prefix(action))
+    {
+        return (!is_word_continuation(key)).then_some(key);
+    }
+    match url.path().strip_prefix('/')?.strip_prefix(action)? {
+        "" | "/" => Some(url.fragment().unwrap_or_default()),
+        key if key.chars().all(is_key_char) && !is_word_continuation(key) => Some(key),
+        _ => None,
+    }
+}
+
+/// Whether `suffix` continues the action into a longer word, as the `ation-help` of `confirmation-help`.
+///
+/// MEGA keys are base64url, so a real key practically always contains an uppercase letter or `_`; page
+/// names are lowercase words joined by hyphens.
+fn is_word_continuation(suffix: &str) -> bool {
+    !suffix.is_empty()
+        && suffix
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// The decoded `href` value of every `<a>` tag in `html`.
+fn anchor_hrefs(html: &str) -> Vec<String> {
+    let mut hrefs = Vec::new();
+    let mut rest = html;
+
+    while let Some(start) = find_ignore_ascii_case(rest, "<a") {
+        rest = &rest[start + 2..];
+        if !rest.starts_with(|c: char| c.is_ascii_whitespace()) {
+            continue;
+        }
+        let (attributes, remaining) = scan_attributes(rest);
+        rest = remaining;
+        if let Some((_, value)) = attributes
+            .into_iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("href"))
+        {
+            hrefs.push(decode_entities(&value).trim().to_string());
+        }
+    }
+    hrefs
+}
+
+/// Read the attributes of a start tag up to its closing `>`, returning them and the text after the tag.
+fn scan_attributes(tag: &str) -> (Vec<(String, String)>, &str) {
+    let mut attributes = Vec::new();
+    let mut chars = tag.char_indices().peekable();
+
+    loop {
+        while chars
+            .next_if(|(_, c)| c.is_ascii_whitespace() || *c == '/')
+            .is_some()
+        {}
+        let Some(&(name_start, c)) = chars.peek() else {
+            return (attributes, "");
+        };
+        if c == '>' {
+            return (attributes, &tag[name_start + 1..]);
+        }
+
+        let mut name_end = name_start;
+        while let Some((i, c)) =
+            chars.next_if(|(_, c)| !c.is_ascii_whitespace() && !matches!(c, '=' | '>' | '/'))
+        {
+            name_end = i + c.len_utf8();
+        }
+        let name = tag[name_start..name_end].to_string();
+        if name.is_empty() {
+            // A stray `=`; skip it so scanning always makes progress
+            chars.next();
+            continue;
+        }
+
+        while chars.next_if(|(_, c)| c.is_ascii_whitespace()).is_some() {}
+        if chars.next_if(|(_, c)| *c == '=').is_none() {
+            attributes.push((name, String::new()));
+            continue;
+        }
+        while chars.next_if(|(_, c)| c.is_ascii_whitespace()).is_some() {}
+
+        let value = match chars.next_if(|(_, c)| matches!(c, '"' | '\'')) {
+            Some((open, quote)) => {
+                let start = open + 1;
+                let end = chars
+                    .find(|(_, c)| *c == quote)
+                    .map_or(tag.len(), |(i, _)| i);
+                &tag[start..end]
+            }
+            None => {
+                let start = chars.peek().map_or(tag.len(), |(i, _)| *i);
+                let mut end = start;
+                while let Some((i, c)) =
+                    chars.next_if(|(_, c)| !c.is_ascii_whitespace() && *c != '>')
+                {
+                    end = i + c.len_utf8();
+                }
+                &tag[start..end]
+            }
+        };
+        attributes.push((name, value.to_string()));
+    }
+}
+
+fn find_ignore_ascii_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Decode the HTML character references that appear in links: `&amp;`, `&lt;`, `&gt;`, `&quot;`,
+/// `&apos;`, `&nbsp;`, and numeric references. Anything else is left as it is.
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..end])?, end)));
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    let code = if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+        u32::from_str_radix(hex, 16).ok()?
+    } else if let Some(decimal) = name.strip_prefix('#') {
+        decimal.parse().ok()?
+    } else {
+        return match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => None,
+        };
+    };
+    char::from_u32(code)
+}
+
+fn is_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Parse a `kind` action link or bare key supplied by a person rather than read from an inbox.
+///
+/// Input containing `://` is treated as a link and checked exactly like a link found in an email body
+/// (see [`extract_link_key`]). Anything else is treated as a bare key, optionally prefixed with the action
+/// (such as `#confirm` or `confirm`) as copied from the link fragment.
+///
+/// Returns `Ok(None)` when the input is empty or a link that is not a `kind` action link.
+pub(crate) fn parse_manual_link(
+    input: &str,
+    kind: LinkKind,
+) -> Result<Option<String>, SuspicionReason> {
+    let input = input.trim();
+    if input.contains("://") {
+        return extract_link_key(input, kind);
+    }
+
+    let key = input.strip_prefix('#').unwrap_or(input);
+    let key = key.strip_prefix(kind.action()).unwrap_or(key);
+    if key.is_empty() {
+        return Ok(None);
+    }
+    if !key.chars().all(is_key_char) {
+        return Err(SuspicionReason::MalformedKey);
+    }
+    Ok(Some(key.to_string()))
+}
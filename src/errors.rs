@@ -1,3 +1,7 @@
+use crate::password::PasswordWeakness;
+use crate::pending::PendingRegistration;
+use crate::vault::VaultError;
+use secrecy::SecretString;
 use thiserror::Error;
 
 /// Errors returned by account generation operations.
@@ -7,16 +11,20 @@ pub enum Error {
     ///
     /// This covers failures while creating the temporary address, polling the inbox, or fetching message bodies.
     ///
-    /// Note: inbox deletion is attempted on a best-effort basis after successful confirmation, and deletion
-    /// failures are ignored (they are not surfaced as an error).
+    /// Note: failing to delete a temporary inbox is never returned as an error. It is reported as a
+    /// [`Warning::InboxNotDeleted`] instead.
+    ///
+    /// Transport errors carry no request URL, since GuerrillaMail URLs include the session token.
     #[error("GuerrillaMail error: {0}")]
-    Mail(#[from] guerrillamail_client::Error),
+    Mail(#[source] guerrillamail_client::Error),
 
     /// MEGA API or transport error.
     ///
     /// This covers failures during account registration and during verification/confirmation.
+    ///
+    /// Transport errors carry no request URL, since authenticated MEGA URLs include the session ID.
     #[error("MEGA error: {0}")]
-    Mega(#[from] megalib::MegaError),
+    Mega(#[source] megalib::MegaError),
 
     /// No likely MEGA confirmation email was observed before the configured timeout elapsed.
     #[error("Timeout waiting for confirmation email")]
@@ -25,6 +33,401 @@ pub enum Error {
     /// A likely MEGA email was observed, but no confirmation key could be extracted from its body.
     #[error("No confirmation link found in email")]
     NoConfirmationLink,
+
+    /// A message presented itself as a MEGA email but failed sender or link validation, and no valid one
+    /// arrived before the deadline.
+    ///
+    /// Anyone who knows a temporary alias can send mail to it, so such messages are never passed to MEGA.
+    /// Polling skips them and goes on; this error names the first one skipped.
+    #[error("Suspicious email from {from:?}: {reason}")]
+    SuspiciousEmail {
+        /// Sender reported by the mail provider.
+        from: String,
+        /// Why the message was rejected.
+        reason: SuspicionReason,
+    },
+
+    /// A link or key passed to [`crate::AccountGenerator::confirm_manually`] or
+    /// [`crate::AccountGenerator::confirm_email_change`] was rejected.
+    #[error("Invalid confirmation link: {0}")]
+    InvalidConfirmationLink(SuspicionReason),
+
+    /// A stage of the generation flow failed.
+    ///
+    /// Every error raised while a [`Stage`] runs is wrapped in this variant, except [`Error::Cancelled`] and
+    /// [`Error::StageTimeout`], which carry the stage themselves. Match on [`Error::root`] to inspect the
+    /// underlying error, and use [`Error::kind`] or [`Error::is_retryable`] to decide how to react.
+    #[error("{stage} failed: {source}")]
+    StageFailed {
+        /// Stage that failed.
+        stage: Stage,
+        /// Address being registered, if known.
+        email: Option<String>,
+        /// What went wrong.
+        #[source]
+        source: Box<Error>,
+    },
+
+    /// Generation was cancelled through its [`tokio_util::sync::CancellationToken`].
+    ///
+    /// If a temporary inbox had been created, its deletion was attempted before this error was returned.
+    /// When `stage` is [`Stage::AwaitingConfirmation`] or [`Stage::Verifying`], MEGA has accepted the
+    /// registration and an unconfirmed account may exist for `email`.
+    #[error("Cancelled during {stage}")]
+    Cancelled {
+        /// Stage that was running when cancellation was observed.
+        stage: Stage,
+        /// Temporary address, if the inbox had been created.
+        email: Option<String>,
+    },
+
+    /// Verification kept failing, or ran out of time, after the confirmation key had been extracted.
+    ///
+    /// A timeout during [`Stage::Verifying`] is reported here with an [`Error::StageTimeout`] `source`. The
+    /// registration is still pending on MEGA's side. Retry later by passing `confirm_key` to
+    /// [`crate::AccountGenerator::confirm_manually`] together with `pending`. When returned by
+    /// [`crate::AccountGenerator::generate`], the temporary inbox has already been deleted, so `pending` cannot
+    /// be resumed; [`crate::AccountGenerator::resume`] keeps the inbox until `pending.deadline`.
+    #[error("Verification failed after {attempts} attempt(s): {source}")]
+    VerificationFailed {
+        /// Registration that could not be verified.
+        pending: Box<PendingRegistration>,
+        /// Confirmation key extracted from MEGA's email.
+        confirm_key: SecretString,
+        /// Number of verification attempts made.
+        attempts: u32,
+        /// Error from the last attempt, or the timeout that interrupted verification.
+        #[source]
+        source: Box<Error>,
+    },
+
+    /// A stage did not finish within its configured stage timeout or the total timeout.
+    ///
+    /// See [`crate::AccountGeneratorBuilder::stage_timeout`] and
+    /// [`crate::AccountGeneratorBuilder::total_timeout`]. Deletion of the temporary inbox, if one had been
+    /// created, was attempted before this error was returned. When `stage` is
+    /// [`Stage::AwaitingConfirmation`] or [`Stage::Verifying`], an unconfirmed account may exist for `email`.
+    #[error("Timed out during {stage}")]
+    StageTimeout {
+        /// Stage that was running when time ran out.
+        stage: Stage,
+        /// Address being registered, if known.
+        email: Option<String>,
+    },
+
+    /// The password was rejected by the configured [`crate::PasswordPolicy`].
+    ///
+    /// The check runs before any network request, so no inbox was created and nothing was registered.
+    #[error("Weak password: {0}")]
+    WeakPassword(PasswordWeakness),
+
+    /// A proxy URL passed to [`crate::ProxyConfig::parse`] was rejected. The message never includes credentials.
+    #[error("Invalid proxy: {0}")]
+    InvalidProxy(String),
+
+    /// A [`crate::PendingRegistration`] could not be parsed.
+    #[error("Invalid pending registration: {0}")]
+    InvalidPendingRegistration(String),
+
+    /// A local file could not be read or written.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// A credential vault could not be created, unlocked, read or written.
+    #[error("Vault error: {0}")]
+    Vault(#[from] VaultError),
+}
+
+// Request URLs can carry session IDs (MEGA's `sid`, GuerrillaMail's `sid_token`) and `reqwest` includes the
+// URL in its `Display` output, so it is removed before an error can be shown or logged.
+impl From<guerrillamail_client::Error> for Error {
+    fn from(e: guerrillamail_client::Error) -> Self {
+        use guerrillamail_client::Error as MailError;
+
+        Error::Mail(match e {
+            MailError::Request(e) => MailError::Request(e.without_url()),
+            e => e,
+        })
+    }
+}
+
+impl From<megalib::MegaError> for Error {
+    fn from(e: megalib::MegaError) -> Self {
+        use megalib::MegaError;
+
+        Error::Mega(match e {
+            MegaError::RequestError(e) => MegaError::RequestError(e.without_url()),
+            e => e,
+        })
+    }
+}
+
+impl Error {
+    /// The innermost error, looking through any [`Error::StageFailed`] wrappers.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::StageFailed { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    /// Stage that was running when the error occurred, if it happened inside the generation flow.
+    pub fn stage(&self) -> Option<Stage> {
+        match self {
+            Error::StageFailed { stage, .. }
+            | Error::Cancelled { stage, .. }
+            | Error::StageTimeout { stage, .. } => Some(*stage),
+            _ => None,
+        }
+    }
+
+    /// Address being registered when the error occurred, if known.
+    pub fn email(&self) -> Option<&str> {
+        match self {
+            Error::StageFailed { email, .. }
+            | Error::Cancelled { email, .. }
+            | Error::StageTimeout { email, .. } => email.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Whether MEGA had accepted the registration when the error occurred.
+    ///
+    /// When this returns `true`, an unconfirmed account exists for [`Error::email`]. It can be confirmed later
+    /// from a saved [`crate::PendingRegistration`], or is left to expire.
+    pub fn leaves_unconfirmed_account(&self) -> bool {
+        matches!(
+            self.stage(),
+            Some(Stage::AwaitingConfirmation | Stage::Verifying)
+        )
+    }
+
+    /// Classify the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Mail(e) => mail_error_kind(e),
+            Error::Mega(e) => mega_error_kind(e),
+            Error::EmailTimeout | Error::StageTimeout { .. } => ErrorKind::Timeout,
+            Error::NoConfirmationLink
+            | Error::SuspiciousEmail { .. }
+            | Error::InvalidConfirmationLink(_) => ErrorKind::InvalidConfirmation,
+            Error::StageFailed { source, .. } | Error::VerificationFailed { source, .. } => {
+                source.kind()
+            }
+            Error::Cancelled { .. } => ErrorKind::Cancelled,
+            Error::WeakPassword(_)
+            | Error::InvalidProxy(_)
+            | Error::InvalidPendingRegistration(_)
+            | Error::Io(_)
+            | Error::Vault(_) => ErrorKind::Local,
+        }
+    }
+
+    /// Whether repeating the failed operation may succeed.
+    ///
+    /// This is `true` for the [`ErrorKind::Network`], [`ErrorKind::RateLimited`], [`ErrorKind::Unavailable`]
+    /// and [`ErrorKind::Timeout`] kinds. Rate limits call for waiting before the next attempt.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind(),
+            ErrorKind::Network
+                | ErrorKind::RateLimited
+                | ErrorKind::Unavailable
+                | ErrorKind::Timeout
+        )
+    }
+}
+
+/// Broad classification of an error, returned by [`Error::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A request could not be completed: connection, DNS, TLS or transport timeout failure.
+    Network,
+    /// A service asked for fewer requests, such as HTTP 429 or MEGA's `ERATELIMIT` and `ETOOMANY`.
+    RateLimited,
+    /// A service failed temporarily, such as HTTP 5xx or MEGA's `EAGAIN`, `EINTERNAL` and `ETEMPUNAVAIL`.
+    Unavailable,
+    /// A service refused the request. Repeating it unchanged fails again.
+    Rejected,
+    /// A service returned a response that could not be understood.
+    Protocol,
+    /// No usable confirmation email arrived in time, or a stage ran out of time.
+    Timeout,
+    /// A confirmation email or link was missing, malformed or untrusted.
+    InvalidConfirmation,
+    /// The operation was cancelled by the caller.
+    Cancelled,
+    /// A local file or input, such as a weak password, could not be used.
+    Local,
+}
+
+fn mail_error_kind(e: &guerrillamail_client::Error) -> ErrorKind {
+    use guerrillamail_client::Error as MailError;
+
+    match e {
+        MailError::Request(e) => match e.status() {
+            Some(status) => http_status_kind(status.as_u16()),
+            None => ErrorKind::Network,
+        },
+        MailError::ResponseParse(_) | MailError::TokenParse | MailError::Json(_) => {
+            ErrorKind::Protocol
+        }
+        MailError::HeaderValue(_) => ErrorKind::Local,
+    }
+}
+
+fn mega_error_kind(e: &megalib::MegaError) -> ErrorKind {
+    use megalib::MegaError;
+
+    match e {
+        MegaError::HttpError(status) => http_status_kind(*status),
+        MegaError::RequestError(e) => match e.status() {
+            Some(status) => http_status_kind(status.as_u16()),
+            None => ErrorKind::Network,
+        },
+        MegaError::ServerBusy => ErrorKind::Unavailable,
+        MegaError::ApiError { code, .. } => mega_api_code_kind(*code),
+        MegaError::InvalidChallenge => ErrorKind::Rejected,
+        _ => ErrorKind::Protocol,
+    }
+}
+
+/// Classify a MEGA API error code, using the meanings from MEGA's SDK.
+fn mega_api_code_kind(code: i32) -> ErrorKind {
+    match code {
+        // EINTERNAL, EAGAIN, ETEMPUNAVAIL
+        -1 | -3 | -18 => ErrorKind::Unavailable,
+        // ERATELIMIT, ETOOMANY
+        -4 | -6 => ErrorKind::RateLimited,
+        // EARGS, EFAILED, ERANGE, EEXPIRED, ENOENT, ECIRCULAR, EACCESS, EEXIST, EINCOMPLETE, EKEY, ESID,
+        // EBLOCKED, EOVERQUOTA
+        -2 | -5 | -7 | -8 | -9 | -10 | -11 | -12 | -13 | -14 | -15 | -16 | -17 => {
+            ErrorKind::Rejected
+        }
+        _ => ErrorKind::Protocol,
+    }
+}
+
+fn http_status_kind(status: u16) -> ErrorKind {
+    match status {
+        429 => ErrorKind::RateLimited,
+        500..=599 => ErrorKind::Unavailable,
+        _ => ErrorKind::Rejected,
+    }
+}
+
+/// Stage of the account generation flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// Creating the temporary inbox.
+    CreatingInbox,
+    /// Submitting the registration to MEGA.
+    Registering,
+    /// Polling the inbox for the confirmation email.
+    AwaitingConfirmation,
+    /// Confirming the registration with MEGA.
+    Verifying,
+    /// Logging in to the confirmed account.
+    LoggingIn,
+    /// Exporting the recovery key of the confirmed account.
+    ExportingRecoveryKey,
+    /// Moving a confirmed account to a new email address.
+    ChangingEmail,
+    /// Permanently deleting an account.
+    CancellingAccount,
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Stage::CreatingInbox => "inbox creation",
+            Stage::Registering => "registration",
+            Stage::AwaitingConfirmation => "confirmation polling",
+            Stage::Verifying => "verification",
+            Stage::LoggingIn => "login",
+            Stage::ExportingRecoveryKey => "recovery key export",
+            Stage::ChangingEmail => "email change",
+            Stage::CancellingAccount => "account cancellation",
+        })
+    }
+}
+
+/// Why a message was reported as [`Error::SuspiciousEmail`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SuspicionReason {
+    /// The sender is not an address at a MEGA domain.
+    #[error("sender is not a verified MEGA address")]
+    UntrustedSender,
+
+    /// A confirmation link points at a host that does not belong to MEGA.
+    #[error("confirmation link points to untrusted host {0:?}")]
+    UntrustedLinkHost(String),
+
+    /// A confirmation link does not use `https`.
+    #[error("confirmation link does not use https")]
+    InsecureLink,
+
+    /// A confirmation link carries a key with characters outside the base64url alphabet.
+    #[error("confirmation key is malformed")]
+    MalformedKey,
+
+    /// The message contains confirmation links with different keys.
+    #[error("message contains conflicting confirmation keys")]
+    ConflictingKeys,
+}
+
+/// A problem that did not stop an operation, but may need attention.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Warning {
+    /// A temporary inbox could not be deleted.
+    ///
+    /// The inbox is listed by [`crate::AccountGenerator::leftover_inboxes`] until
+    /// [`crate::AccountGenerator::cleanup`] deletes it.
+    #[error("inbox {email} was not deleted: {reason}")]
+    InboxNotDeleted {
+        /// Address of the inbox.
+        email: String,
+        /// Error from the mail provider, or why the deletion was not confirmed.
+        reason: String,
+    },
+
+    /// The login check of a confirmed account failed, so it has no
+    /// [`session`](crate::GeneratedAccount::session).
+    ///
+    /// See [`crate::AccountGeneratorBuilder::login_check`].
+    #[error("login check for {email} failed: {reason}")]
+    LoginFailed {
+        /// Address of the account.
+        email: String,
+        /// Why the login failed.
+        reason: String,
+    },
+
+    /// A message presented itself as a MEGA email but failed sender or link validation, and was skipped.
+    ///
+    /// See [`Error::SuspiciousEmail`].
+    #[error("skipped suspicious email from {from:?}: {reason}")]
+    SuspiciousEmailSkipped {
+        /// Sender reported by the mail provider.
+        from: String,
+        /// Why the message was rejected.
+        reason: String,
+    },
+
+    /// The recovery key of a confirmed account could not be exported, so it has no
+    /// [`recovery_key`](crate::GeneratedAccount::recovery_key).
+    ///
+    /// See [`crate::AccountGeneratorBuilder::export_recovery_key`].
+    #[error("recovery key for {email} was not exported: {reason}")]
+    RecoveryKeyNotExported {
+        /// Address of the account.
+        email: String,
+        /// Why the export failed.
+        reason: String,
+    },
 }
 
 /// Crate-local result type.
@@ -0,0 +1,117 @@
+//! Structured export of generated credentials.
+//!
+//! Two line-oriented formats are supported, both carrying a `schema_version` field so consumers can detect
+//! layout changes:
+//!
+//! - JSON Lines: one object per line, `{"schema_version":1,"email":"...","password":"...","name":"..."}`
+//! - CSV (RFC 4180): header `schema_version,email,password,name`, one row per account
+//!
+//! Both formats contain plaintext passwords. Prefer [`crate::vault`] for storage at rest.
+
+use crate::account::GeneratedAccount;
+use crate::vault::VaultEntry;
+use secrecy::{ExposeSecret, SecretString};
+use std::io::Write;
+
+/// Schema version written into every exported record.
+///
+/// Incremented whenever fields are renamed, removed or change meaning. Adding fields does not change it.
+pub const EXPORT_SCHEMA_VERSION: u32 = 1;
+
+const CSV_HEADER: &str = "schema_version,email,password,name";
+
+/// Supported export formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One JSON object per line.
+    JsonLines,
+    /// Comma-separated values with a header row.
+    Csv,
+}
+
+/// Borrowed view of the credentials written by [`ExportFormat::write_record`].
+#[derive(Debug, Clone, Copy)]
+pub struct ExportRecord<'a> {
+    /// Account email address.
+    pub email: &'a str,
+    /// Account password.
+    pub password: &'a SecretString,
+    /// Account display name.
+    pub name: &'a str,
+}
+
+impl<'a> From<&'a GeneratedAccount> for ExportRecord<'a> {
+    fn from(account: &'a GeneratedAccount) -> Self {
+        Self {
+            email: &account.email,
+            password: &account.password,
+            name: &account.name,
+        }
+    }
+}
+
+impl<'a> From<&'a VaultEntry> for ExportRecord<'a> {
+    fn from(entry: &'a VaultEntry) -> Self {
+        Self {
+            email: &entry.email,
+            password: &entry.password,
+            name: &entry.name,
+        }
+    }
+}
+
+impl ExportFormat {
+    /// Write the header that precedes the first record, if the format has one.
+    ///
+    /// Call this once when starting a new file; appending to an existing file should skip it.
+    pub fn write_header(self, out: &mut impl Write) -> std::io::Result<()> {
+        match self {
+            ExportFormat::JsonLines => Ok(()),
+            ExportFormat::Csv => write!(out, "{}\r\n", CSV_HEADER),
+        }
+    }
+
+    /// Write one record, including the trailing newline.
+    pub fn write_record(
+        self,
+        out: &mut impl Write,
+        record: ExportRecord<'_>,
+    ) -> std::io::Result<()> {
+        let password = record.password.expose_secret();
+        match self {
+            ExportFormat::JsonLines => writeln!(
+                out,
+                "{{\"schema_version\":{},\"email\":{},\"password\":{},\"name\":{}}}",
+                EXPORT_SCHEMA_VERSION,
+                json_string(record.email),
+                json_string(password),
+                json_string(record.name)
+            ),
+            ExportFormat::Csv => write!(
+                out,
+                "{},{},{},{}\r\n",
+                EXPORT_SCHEMA_VERSION,
+                csv_field(record.email),
+                csv_field(password),
+                csv_field(record.name)
+            ),
+        }
+    }
+}
+
+/// Quote `value` as a JSON string.
+fn json_string(value: &str) -> String {
+    serde_json::to_string(value).expect("strings always serialize")
+}
+
+/// Quote `value` as a CSV field when it contains a delimiter, quote, line break or surrounding whitespace.
+fn csv_field(value: &str) -> String {
+    let needs_quotes = value.contains([',', '"', '\n', '\r'])
+        || value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace);
+    if needs_quotes {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
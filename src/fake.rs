@@ -0,0 +1,451 @@
+use crate::account::AccountSession;
+use crate::errors::{Error, Result};
+use crate::mail::{InboxMessage, MailProvider};
+use crate::registrar::Registrar;
+use megalib::{MegaError, RegistrationState};
+use secrecy::SecretString;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Sender used by [`FakeRegistrar`] for the confirmation emails it delivers.
+pub const FAKE_MEGA_SENDER: &str = "welcome@mega.nz";
+
+/// In-memory [`MailProvider`] for tests.
+///
+/// Inboxes live in shared state, so clones of a `FakeMailProvider` observe the same messages. Like a
+/// GuerrillaMail session, the provider reads only the inbox it was last attached to by
+/// [`MailProvider::create_email`] or [`MailProvider::attach`]; other inboxes look empty. Pair it with
+/// [`FakeRegistrar::new`] to have confirmation emails delivered automatically, or use
+/// [`FakeMailProvider::deliver`] to place arbitrary messages in an inbox.
+#[derive(Debug, Clone, Default)]
+pub struct FakeMailProvider {
+    state: Arc<Mutex<FakeMailState>>,
+}
+
+#[derive(Debug, Default)]
+struct FakeMailState {
+    inboxes: HashMap<String, Vec<FakeMessage>>,
+    deleted: Vec<String>,
+    current: Option<String>,
+    next_id: u64,
+}
+
+#[derive(Debug, Clone)]
+struct FakeMessage {
+    id: String,
+    from: String,
+    subject: String,
+    body: String,
+}
+
+impl FakeMailProvider {
+    /// Domain appended to aliases passed to [`MailProvider::create_email`].
+    pub const DOMAIN: &'static str = "fakemail.test";
+
+    /// Create an empty fake mail provider.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Place a message in the inbox for `email`, creating the inbox if needed.
+    pub fn deliver(&self, email: &str, from: &str, subject: &str, body: &str) {
+        let mut state = self.lock();
+        state.next_id += 1;
+        let message = FakeMessage {
+            id: state.next_id.to_string(),
+            from: from.to_string(),
+            subject: subject.to_string(),
+            body: body.to_string(),
+        };
+        state
+            .inboxes
+            .entry(email.to_string())
+            .or_default()
+            .push(message);
+    }
+
+    /// Addresses of inboxes that currently exist.
+    pub fn inboxes(&self) -> Vec<String> {
+        self.lock().inboxes.keys().cloned().collect()
+    }
+
+    /// Address of the inbox the provider is attached to, if any.
+    pub fn current(&self) -> Option<String> {
+        self.lock().current.clone()
+    }
+
+    /// Addresses passed to [`MailProvider::delete_email`], in call order.
+    pub fn deleted(&self) -> Vec<String> {
+        self.lock().deleted.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FakeMailState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl FakeMailState {
+    /// Messages in the inbox for `email`, or none if the provider is attached to another inbox.
+    fn readable(&self, email: &str) -> &[FakeMessage] {
+        if self
+            .current
+            .as_deref()
+            .is_some_and(|current| current != email)
+        {
+            return &[];
+        }
+        self.inboxes
+            .get(email)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+impl MailProvider for FakeMailProvider {
+    async fn create_email(&self, alias: &str) -> Result<String> {
+        let email = format!("{}@{}", alias, Self::DOMAIN);
+        let mut state = self.lock();
+        state.inboxes.entry(email.clone()).or_default();
+        state.current = Some(email.clone());
+        Ok(email)
+    }
+
+    async fn attach(&self, email: &str) -> Result<()> {
+        self.lock().current = Some(email.to_string());
+        Ok(())
+    }
+
+    async fn get_messages(&self, email: &str) -> Result<Vec<InboxMessage>> {
+        let state = self.lock();
+        Ok(state
+            .readable(email)
+            .iter()
+            .map(|msg| InboxMessage {
+                id: msg.id.clone(),
+                from: msg.from.clone(),
+                subject: msg.subject.clone(),
+            })
+            .collect())
+    }
+
+    async fn fetch_body(&self, email: &str, id: &str) -> Result<String> {
+        self.lock()
+            .readable(email)
+            .iter()
+            .find(|msg| msg.id == id)
+            .map(|msg| msg.body.clone())
+            .ok_or(Error::Mail(guerrillamail_client::Error::ResponseParse(
+                "unknown message id",
+            )))
+    }
+
+    async fn delete_email(&self, email: &str) -> Result<bool> {
+        let mut state = self.lock();
+        state.deleted.push(email.to_string());
+        Ok(state.inboxes.remove(email).is_some())
+    }
+}
+
+/// In-memory [`Registrar`] for tests.
+///
+/// Each registration is assigned a unique confirmation key. When constructed with [`FakeRegistrar::new`],
+/// a MEGA-style confirmation email containing that key is delivered to the registered address, so the
+/// generator's full register, poll, extract and verify path runs without network access.
+///
+/// Verified accounts can log in with the password they were registered with, and report a
+/// [`FakeRegistrar::STORAGE_TOTAL`] quota with nothing used, and export a recovery key unique to the
+/// account. Requesting an email change delivers a
+/// `#verify<KEY>` link to the new address; confirming it moves the account to that address. Requesting
+/// cancellation delivers a `#cancel<KEY>` link to the account's address; confirming it deletes the account.
+#[derive(Debug, Clone, Default)]
+pub struct FakeRegistrar {
+    mail: Option<FakeMailProvider>,
+    state: Arc<Mutex<FakeRegistrarState>>,
+}
+
+#[derive(Debug, Default)]
+struct FakeRegistrarState {
+    pending: HashMap<String, FakeRegistration>,
+    verified: Vec<String>,
+    accounts: HashMap<String, FakeAccount>,
+    email_changes: HashMap<String, FakeEmailChange>,
+    cancellations: HashMap<String, String>,
+    cancelled: Vec<String>,
+    next_id: u64,
+}
+
+#[derive(Debug, Clone)]
+struct FakeAccount {
+    password: String,
+    recovery_key: String,
+}
+
+#[derive(Debug, Clone)]
+struct FakeEmailChange {
+    email: String,
+    new_email: String,
+}
+
+#[derive(Debug, Clone)]
+struct FakeRegistration {
+    email: String,
+    password: String,
+    confirm_key: String,
+}
+
+impl FakeRegistrar {
+    /// Storage quota reported for every fake account: 20 GiB.
+    pub const STORAGE_TOTAL: u64 = 20 * 1024 * 1024 * 1024;
+
+    /// Create a registrar that delivers confirmation emails through `mail`.
+    pub fn new(mail: FakeMailProvider) -> Self {
+        Self {
+            mail: Some(mail),
+            state: Arc::default(),
+        }
+    }
+
+    /// Create a registrar that accepts registrations but never sends a confirmation email.
+    pub fn silent() -> Self {
+        Self::default()
+    }
+
+    /// Confirmation key issued for `email`, if it has a pending registration.
+    pub fn confirm_key(&self, email: &str) -> Option<String> {
+        self.lock()
+            .pending
+            .values()
+            .find(|reg| reg.email == email)
+            .map(|reg| reg.confirm_key.clone())
+    }
+
+    /// Key of the pending email change to `new_email`, if one was requested.
+    pub fn email_change_key(&self, new_email: &str) -> Option<String> {
+        self.lock()
+            .email_changes
+            .iter()
+            .find(|(_, change)| change.new_email == new_email)
+            .map(|(key, _)| key.clone())
+    }
+
+    /// Addresses of accounts that were cancelled, in order.
+    pub fn cancelled(&self) -> Vec<String> {
+        self.lock().cancelled.clone()
+    }
+
+    /// Addresses whose registrations were successfully verified, in order.
+    pub fn verified(&self) -> Vec<String> {
+        self.lock().verified.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FakeRegistrarState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl FakeRegistrarState {
+    fn check_password(&self, email: &str, password: &str) -> Result<()> {
+        if self
+            .accounts
+            .get(email)
+            .map(|account| account.password.as_str())
+            != Some(password)
+        {
+            // MEGA's ENOENT, returned for unknown or unconfirmed accounts and wrong passwords
+            return Err(MegaError::ApiError {
+                code: -9,
+                message: "Resource does not exist".to_string(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl Registrar for FakeRegistrar {
+    async fn register(
+        &self,
+        email: &str,
+        password: &str,
+        _name: &str,
+    ) -> Result<RegistrationState> {
+        let (user_handle, confirm_key) = {
+            let mut state = self.lock();
+            state.next_id += 1;
+            let user_handle = format!("fakeuser{}", state.next_id);
+            let confirm_key = format!("FakeConfirmKey{}", state.next_id);
+            state.pending.insert(
+                user_handle.clone(),
+                FakeRegistration {
+                    email: email.to_string(),
+                    password: password.to_string(),
+                    confirm_key: confirm_key.clone(),
+                },
+            );
+            (user_handle, confirm_key)
+        };
+
+        if let Some(mail) = &self.mail {
+            let body = format!(
+                "<p>Please confirm your email address.</p>\
+                 <a href=\"https://mega.nz/#confirm{}\">Verify my email</a>",
+                confirm_key
+            );
+            mail.deliver(
+                email,
+                FAKE_MEGA_SENDER,
+                "MEGA email verification required",
+                &body,
+            );
+        }
+
+        Ok(RegistrationState {
+            user_handle,
+            password_key: [0; 16],
+            challenge: [0; 16],
+        })
+    }
+
+    async fn verify(&self, state: &RegistrationState, confirm_key: &str) -> Result<()> {
+        let mut inner = self.lock();
+        let registration = inner
+            .pending
+            .get(&state.user_handle)
+            .cloned()
+            .ok_or_else(|| MegaError::InvalidState("unknown user handle".to_string()))?;
+        if registration.confirm_key != confirm_key {
+            return Err(MegaError::InvalidChallenge.into());
+        }
+        inner.pending.remove(&state.user_handle);
+        inner.accounts.insert(
+            registration.email.clone(),
+            FakeAccount {
+                password: registration.password,
+                recovery_key: format!("FakeRecoveryKey{}", state.user_handle),
+            },
+        );
+        inner.verified.push(registration.email);
+        Ok(())
+    }
+
+    async fn login(&self, email: &str, password: &str) -> Result<AccountSession> {
+        let mut inner = self.lock();
+        inner.check_password(email, password)?;
+        inner.next_id += 1;
+        Ok(AccountSession {
+            session: SecretString::from(format!("FakeSession{}", inner.next_id)),
+            storage_total: Self::STORAGE_TOTAL,
+            storage_used: 0,
+        })
+    }
+
+    async fn recovery_key(&self, email: &str, password: &str) -> Result<SecretString> {
+        let inner = self.lock();
+        inner.check_password(email, password)?;
+        Ok(SecretString::from(
+            inner.accounts[email].recovery_key.clone(),
+        ))
+    }
+
+    async fn request_cancellation(&self, email: &str, password: &str) -> Result<()> {
+        let key = {
+            let mut inner = self.lock();
+            inner.check_password(email, password)?;
+            inner.next_id += 1;
+            let key = format!("FakeCancelKey{}", inner.next_id);
+            inner.cancellations.insert(key.clone(), email.to_string());
+            key
+        };
+
+        if let Some(mail) = &self.mail {
+            let body = format!(
+                "<p>You have requested to cancel your account.</p>\
+                 <a href=\"https://mega.nz/#cancel{}\">Cancel my account</a>",
+                key
+            );
+            mail.deliver(email, FAKE_MEGA_SENDER, "MEGA account cancellation", &body);
+        }
+        Ok(())
+    }
+
+    async fn confirm_cancellation(&self, email: &str, password: &str, key: &str) -> Result<()> {
+        let mut inner = self.lock();
+        inner.check_password(email, password)?;
+        if inner.cancellations.get(key).map(String::as_str) != Some(email) {
+            // MEGA's EKEY
+            return Err(MegaError::ApiError {
+                code: -14,
+                message: "Invalid key".to_string(),
+            }
+            .into());
+        }
+        inner.cancellations.remove(key);
+        inner.accounts.remove(email);
+        inner.cancelled.push(email.to_string());
+        Ok(())
+    }
+
+    async fn request_email_change(
+        &self,
+        email: &str,
+        password: &str,
+        new_email: &str,
+    ) -> Result<()> {
+        let key = {
+            let mut inner = self.lock();
+            inner.check_password(email, password)?;
+            inner.next_id += 1;
+            let key = format!("FakeVerifyKey{}", inner.next_id);
+            inner.email_changes.insert(
+                key.clone(),
+                FakeEmailChange {
+                    email: email.to_string(),
+                    new_email: new_email.to_string(),
+                },
+            );
+            key
+        };
+
+        if let Some(mail) = &self.mail {
+            let body = format!(
+                "<p>Please confirm your new email address.</p>\
+                 <a href=\"https://mega.nz/#verify{}\">Confirm email change</a>",
+                key
+            );
+            mail.deliver(
+                new_email,
+                FAKE_MEGA_SENDER,
+                "MEGA email change verification",
+                &body,
+            );
+        }
+        Ok(())
+    }
+
+    async fn confirm_email_change(
+        &self,
+        email: &str,
+        password: &str,
+        new_email: &str,
+        key: &str,
+    ) -> Result<()> {
+        let mut inner = self.lock();
+        inner.check_password(email, password)?;
+        match inner.email_changes.get(key) {
+            Some(change) if change.email == email && change.new_email == new_email => {}
+            // MEGA's EKEY
+            _ => {
+                return Err(MegaError::ApiError {
+                    code: -14,
+                    message: "Invalid key".to_string(),
+                }
+                .into());
+            }
+        }
+        inner.email_changes.remove(key);
+        if let Some(account) = inner.accounts.remove(email) {
+            inner.accounts.insert(new_email.to_string(), account);
+        }
+        Ok(())
+    }
+}
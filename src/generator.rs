@@ -1,10 +1,27 @@
-use crate::account::GeneratedAccount;
-use crate::errors::{Error, Result};
+use crate::account::{GeneratedAccount, GenerationReport};
+use crate::confirm::{
+    LinkKind, extract_link_key, is_trusted_sender, looks_like_mega, parse_manual_link,
+};
+use crate::errors::{Error, Result, Stage, SuspicionReason, Warning};
+use crate::mail::MailProvider;
+use crate::password::PasswordPolicy;
+use crate::pending::PendingRegistration;
+use crate::progress::{ProgressEvent, ProgressHandler};
+use crate::proxy::ProxyConfig;
 use crate::random::{generate_random_alias, generate_random_name};
+use crate::registrar::{MegaRegistrar, Registrar};
 use guerrillamail_client::Client as MailClient;
-use megalib::{register, verify_registration};
-use regex::Regex;
-use std::time::Duration;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use secrecy::{ExposeSecret, SecretString};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
 /// High-level MEGA account generator.
 ///
@@ -18,11 +35,68 @@ use std::time::Duration;
 /// `proxy`, `timeout`, and `poll_interval`.
 ///
 /// This type is designed to be reused to generate multiple accounts with the same configuration.
-pub struct AccountGenerator {
-    mail_client: MailClient,
+///
+/// The generator is generic over its [`MailProvider`] and [`Registrar`]. [`AccountGeneratorBuilder::build`]
+/// uses GuerrillaMail and MEGA; [`AccountGeneratorBuilder::build_with`] accepts any implementation, such as
+/// the `FakeMailProvider` and `FakeRegistrar` of the `test-util` feature for offline tests.
+pub struct AccountGenerator<M = MailClient, R = MegaRegistrar> {
+    mail_client: M,
+    registrar: R,
     timeout: Duration,
     poll_interval: Duration,
-    proxy: Option<String>,
+    total_timeout: Option<Duration>,
+    stage_timeouts: HashMap<Stage, Duration>,
+    max_retries: u32,
+    retry_backoff: Duration,
+    login_check: bool,
+    export_recovery_key: bool,
+    password_policy: PasswordPolicy,
+    /// Source of aliases and display names.
+    rng: Mutex<StdRng>,
+    /// Temporary inboxes whose deletion failed or never ran.
+    leftover_inboxes: Mutex<Vec<String>>,
+    progress: Option<ProgressHandler>,
+}
+
+/// Limits shared by the stages of one public call.
+struct RunContext<'a> {
+    cancel: &'a CancellationToken,
+    /// End of the total timeout, if one is configured.
+    deadline: Option<Instant>,
+}
+
+/// Temporary inbox owned by a running call.
+///
+/// Dropping the guard without [`InboxGuard::disarm`], for example because the call's future was dropped,
+/// records the inbox in `leftover_inboxes` so [`AccountGenerator::cleanup`] can delete it later.
+struct InboxGuard<'a> {
+    email: Option<String>,
+    leftover_inboxes: &'a Mutex<Vec<String>>,
+}
+
+impl InboxGuard<'_> {
+    fn email(&self) -> &str {
+        self.email.as_deref().expect("guard is armed until dropped")
+    }
+
+    /// Release the inbox without recording it as a leftover.
+    fn disarm(mut self) {
+        self.email = None;
+    }
+}
+
+impl Drop for InboxGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(email) = self.email.take() {
+            let mut leftovers = self
+                .leftover_inboxes
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            if !leftovers.contains(&email) {
+                leftovers.push(email);
+            }
+        }
+    }
 }
 
 /// Builder for [`AccountGenerator`].
@@ -30,12 +104,30 @@ pub struct AccountGenerator {
 /// Defaults:
 /// - `timeout`: 300 seconds
 /// - `poll_interval`: 5 seconds
+/// - `total_timeout`: none
+/// - `stage_timeout`: none for every stage
+/// - `max_retries`: 3
+/// - `retry_backoff`: 1 second
+/// - `login_check`: disabled
+/// - `export_recovery_key`: disabled
+/// - `password_policy`: [`PasswordPolicy::default`]
+/// - `rng_seed`: none (aliases and names are seeded from OS entropy)
 /// - `proxy`: disabled
+/// - `on_progress`: no handler
 #[derive(Debug, Clone)]
 pub struct AccountGeneratorBuilder {
     timeout: Duration,
     poll_interval: Duration,
-    proxy: Option<String>,
+    total_timeout: Option<Duration>,
+    stage_timeouts: HashMap<Stage, Duration>,
+    max_retries: u32,
+    retry_backoff: Duration,
+    login_check: bool,
+    export_recovery_key: bool,
+    password_policy: PasswordPolicy,
+    rng_seed: Option<u64>,
+    proxy: Option<ProxyConfig>,
+    progress: Option<ProgressHandler>,
 }
 
 impl AccountGenerator {
@@ -55,7 +147,9 @@ impl AccountGenerator {
     pub async fn new() -> Result<Self> {
         Self::builder().build().await
     }
+}
 
+impl<M: MailProvider, R: Registrar> AccountGenerator<M, R> {
     /// Generate and confirm a MEGA account using a random display name.
     ///
     /// A random temporary GuerrillaMail alias is always used for the email address.
@@ -66,21 +160,54 @@ impl AccountGenerator {
     /// # Errors
     ///
     /// Returns:
+    /// - [`Error::WeakPassword`] if `password` fails the [`AccountGeneratorBuilder::password_policy`]; this
+    ///   is checked before any network request
     /// - [`Error::Mail`] if GuerrillaMail inbox creation, polling, or message-body fetching fails
-    /// - [`Error::Mega`] if MEGA registration or verification fails
+    /// - [`Error::Mega`] if MEGA registration fails
     /// - [`Error::EmailTimeout`] if no likely MEGA email is observed before `timeout`
     /// - [`Error::NoConfirmationLink`] if a likely MEGA email is observed before `timeout`, but no confirmation
     ///   key can be extracted from its body
+    /// - [`Error::SuspiciousEmail`] if a likely MEGA email is not from a MEGA sender domain, or links to a
+    ///   confirmation URL outside MEGA's hosts, and no valid one arrives before `timeout`. Such messages are
+    ///   skipped while polling goes on, and listed as [`Warning::SuspiciousEmailSkipped`] when a valid one follows
+    /// - [`Error::VerificationFailed`] if MEGA verification fails after retries or runs out of time; it carries
+    ///   the extracted key
+    /// - [`Error::StageTimeout`] if any other stage exceeds its [`AccountGeneratorBuilder::stage_timeout`] or the call
+    ///   exceeds [`AccountGeneratorBuilder::total_timeout`]
     ///
-    /// Polling checks GuerrillaMail every `poll_interval` until `timeout` elapses.
+    /// Apart from [`Error::WeakPassword`], [`Error::StageTimeout`] and [`Error::Cancelled`], these are wrapped
+    /// in [`Error::StageFailed`], which names the stage and the temporary address; match on [`Error::root`]
+    /// to inspect them.
     ///
-    /// The timeout is evaluated at the start of each poll iteration. As a result, total wall-clock time may
-    /// exceed `timeout` by the duration of an in-flight poll request plus up to one `poll_interval` sleep.
+    /// Polling checks GuerrillaMail every `poll_interval` until `timeout` elapses. The timeout is a hard
+    /// bound: an in-flight poll or body fetch is abandoned when it is reached.
     ///
-    /// Cleanup of the temporary inbox is best-effort; deletion errors are ignored after successful confirmation.
+    /// The temporary inbox is deleted whether generation succeeds or fails. Deletion errors never fail the call:
+    /// on success they are returned as [`Warning`]s in the account's [`GeneratedAccount::report`], together
+    /// with the time each step finished and the number of polls. Inboxes that could not be deleted, including
+    /// those of a call whose future was dropped, are kept for [`AccountGenerator::cleanup`].
     pub async fn generate(&self, password: &str) -> Result<GeneratedAccount> {
-        let name = generate_random_name();
-        self.generate_inner(password, name).await
+        self.generate_cancellable(password, &CancellationToken::new())
+            .await
+    }
+
+    /// Like [`AccountGenerator::generate`], but stops early when `cancel` is triggered.
+    ///
+    /// Cancellation is observed promptly at any point, including during an in-flight request or the sleep
+    /// between polls. Once the temporary inbox exists, its deletion is always attempted before returning.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same error variants as [`AccountGenerator::generate`], plus [`Error::Cancelled`] naming
+    /// the stage that was running when `cancel` fired.
+    pub async fn generate_cancellable(
+        &self,
+        password: &str,
+        cancel: &CancellationToken,
+    ) -> Result<GeneratedAccount> {
+        let name = self.random_name();
+        self.generate_inner(password, name, &self.run_context(cancel))
+            .await
     }
 
     /// Generate and confirm a MEGA account with an explicit display name.
@@ -90,74 +217,962 @@ impl AccountGenerator {
     /// # Errors
     ///
     /// Returns the same error variants as [`AccountGenerator::generate`].
+    pub async fn generate_with_name(&self, password: &str, name: &str) -> Result<GeneratedAccount> {
+        self.generate_with_name_cancellable(password, name, &CancellationToken::new())
+            .await
+    }
+
+    /// Like [`AccountGenerator::generate_with_name`], but stops early when `cancel` is triggered.
     ///
-    /// Polling checks GuerrillaMail every `poll_interval` until `timeout` elapses.
+    /// See [`AccountGenerator::generate_cancellable`] for cancellation semantics.
+    pub async fn generate_with_name_cancellable(
+        &self,
+        password: &str,
+        name: &str,
+        cancel: &CancellationToken,
+    ) -> Result<GeneratedAccount> {
+        self.generate_inner(password, name.to_string(), &self.run_context(cancel))
+            .await
+    }
+
+    /// Create a temporary inbox and submit a MEGA registration for it, without waiting for confirmation.
     ///
-    /// The timeout is evaluated at the start of each poll iteration. As a result, total wall-clock time may
-    /// exceed `timeout` by the duration of an in-flight poll request plus up to one `poll_interval` sleep.
-    pub async fn generate_with_name(&self, password: &str, name: &str) -> Result<GeneratedAccount> {
-        self.generate_inner(password, name.to_string()).await
+    /// Uses `name` as the display name, or a random one when `None`. The returned [`PendingRegistration`]
+    /// carries a deadline of now plus `timeout` and can be saved to disk and later passed to
+    /// [`AccountGenerator::resume`], possibly from another process.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WeakPassword`] if `password` fails the password policy, [`Error::Mail`] if the inbox
+    /// cannot be created, [`Error::Mega`] if registration fails, and [`Error::StageTimeout`] if either step
+    /// runs out of time.
+    pub async fn start_registration(
+        &self,
+        password: &str,
+        name: Option<&str>,
+    ) -> Result<PendingRegistration> {
+        let name = name.map_or_else(|| self.random_name(), str::to_string);
+        let cancel = CancellationToken::new();
+        let mut report = GenerationReport::default();
+        self.start_inner(password, name, &self.run_context(&cancel), &mut report)
+            .await
+    }
+
+    /// Poll for the confirmation email of a [`PendingRegistration`] and verify it.
+    ///
+    /// `password` must be the password the registration was started with; it is only used to fill in the
+    /// returned [`GeneratedAccount`]. Polling continues until `pending.deadline`, and the inbox is always
+    /// checked at least once: if the deadline has already passed, the check is allowed one `poll_interval`.
+    /// The mail session is first attached to the saved inbox with [`MailProvider::attach`], so a generator
+    /// other than the one that started the registration can resume it. A configured `total_timeout` counts
+    /// from the start of this call.
+    ///
+    /// The inbox is deleted only when the registration is finished with: on success, on
+    /// [`Error::SuspiciousEmail`], or once `pending.deadline` has passed. Any other failure, including
+    /// [`Error::Cancelled`] and [`Error::StageTimeout`], leaves the inbox in place so `pending` can be
+    /// resumed again.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same error variants as [`AccountGenerator::generate`] for polling and verification.
+    pub async fn resume(
+        &self,
+        pending: &PendingRegistration,
+        password: &str,
+    ) -> Result<GeneratedAccount> {
+        self.resume_cancellable(pending, password, &CancellationToken::new())
+            .await
     }
 
+    /// Like [`AccountGenerator::resume`], but stops early when `cancel` is triggered.
+    ///
+    /// See [`AccountGenerator::generate_cancellable`] for cancellation semantics.
+    pub async fn resume_cancellable(
+        &self,
+        pending: &PendingRegistration,
+        password: &str,
+        cancel: &CancellationToken,
+    ) -> Result<GeneratedAccount> {
+        let mut report = GenerationReport {
+            started_at: Some(SystemTime::now()),
+            ..GenerationReport::default()
+        };
+        let run = self.run_context(cancel);
+        let result = async {
+            self.run_stage(
+                &run,
+                Stage::AwaitingConfirmation,
+                Some(&pending.email),
+                self.mail_client.attach(&pending.email),
+            )
+            .await?;
+            self.confirm_pending(pending, password, &run, &mut report)
+                .await
+        }
+        .await;
+
+        // Cleanup: delete temporary email once the registration cannot be resumed usefully
+        let finished = match &result {
+            Ok(_) => true,
+            Err(e) => {
+                matches!(e.root(), Error::SuspiciousEmail { .. })
+                    || SystemTime::now() >= pending.deadline
+            }
+        };
+        if finished {
+            let inbox = self.guard_inbox(pending.email.clone());
+            self.clean_up(inbox, &mut report).await;
+        }
+        let mut account = result?;
+        account.report = report;
+        Ok(account)
+    }
+
+    #[tracing::instrument(name = "generate", skip_all, fields(name = %account_name))]
     async fn generate_inner(
         &self,
         password: &str,
         account_name: String,
+        run: &RunContext<'_>,
     ) -> Result<GeneratedAccount> {
-        // Generate random alias
-        let alias = generate_random_alias();
+        let mut report = GenerationReport {
+            started_at: Some(SystemTime::now()),
+            ..GenerationReport::default()
+        };
+        let pending = self
+            .start_inner(password, account_name, run, &mut report)
+            .await?;
+        let inbox = self.guard_inbox(pending.email.clone());
 
-        let email = self.mail_client.create_email(&alias).await?;
+        let result = self
+            .confirm_pending(&pending, password, run, &mut report)
+            .await;
 
-        let state = register(&email, password, &account_name, self.proxy.as_deref()).await?;
+        // Cleanup: delete temporary email, whatever the outcome
+        self.clean_up(inbox, &mut report).await;
+        let mut account = result?;
+        account.report = report;
+        Ok(account)
+    }
 
-        // Poll for confirmation email
-        let confirm_key = self.wait_for_confirmation(&email).await?;
+    async fn start_inner(
+        &self,
+        password: &str,
+        account_name: String,
+        run: &RunContext<'_>,
+        report: &mut GenerationReport,
+    ) -> Result<PendingRegistration> {
+        self.check_password(password)?;
 
-        verify_registration(&state, &confirm_key, self.proxy.as_deref()).await?;
+        // Generate random alias
+        let alias = generate_random_alias(&mut *self.lock_rng());
+
+        let email = self
+            .run_stage(
+                run,
+                Stage::CreatingInbox,
+                None,
+                self.mail_client.create_email(&alias),
+            )
+            .await?;
+        let inbox = self.guard_inbox(email.clone());
+        report.inbox_created_at = Some(SystemTime::now());
+        self.emit(ProgressEvent::InboxCreated {
+            email: email.clone(),
+        });
 
-        // Cleanup: delete temporary email
-        let _ = self.mail_client.delete_email(&email).await;
+        let state = self
+            .run_stage(
+                run,
+                Stage::Registering,
+                Some(&email),
+                self.registrar.register(&email, password, &account_name),
+            )
+            .await;
+        // A failed registration leaves an inbox nobody else knows about
+        if state.is_err() {
+            self.clean_up(inbox, report).await;
+        } else {
+            inbox.disarm();
+        }
+        let state = state?;
+        report.registered_at = Some(SystemTime::now());
+        self.emit(ProgressEvent::RegistrationSubmitted {
+            email: email.clone(),
+        });
 
-        Ok(GeneratedAccount {
+        Ok(PendingRegistration {
             email,
-            password: password.to_string(),
             name: account_name,
+            state,
+            deadline: SystemTime::now() + self.timeout,
+        })
+    }
+
+    async fn confirm_pending(
+        &self,
+        pending: &PendingRegistration,
+        password: &str,
+        run: &RunContext<'_>,
+        report: &mut GenerationReport,
+    ) -> Result<GeneratedAccount> {
+        let inbox = Some(pending.email.as_str());
+
+        // Poll for confirmation email
+        let confirm_key = self
+            .run_stage(
+                run,
+                Stage::AwaitingConfirmation,
+                inbox,
+                self.wait_for_link(
+                    &self.mail_client,
+                    &pending.email,
+                    pending.deadline,
+                    LinkKind::Signup,
+                    Stage::AwaitingConfirmation,
+                    Some(&mut *report),
+                ),
+            )
+            .await?;
+
+        self.verify_stage(run, pending, &confirm_key).await?;
+        report.verified_at = Some(SystemTime::now());
+        self.emit(ProgressEvent::Verified {
+            email: pending.email.clone(),
+        });
+
+        Ok(self.finish_account(pending, password, run, report).await)
+    }
+
+    /// Temporary inboxes that could not be deleted, in the order they were left over.
+    ///
+    /// An inbox is listed when its deletion failed or when the call that owned it was dropped before cleaning
+    /// up. Call [`AccountGenerator::cleanup`] to retry.
+    pub fn leftover_inboxes(&self) -> Vec<String> {
+        self.lock_leftover_inboxes().clone()
+    }
+
+    /// Retry deleting every inbox listed by [`AccountGenerator::leftover_inboxes`].
+    ///
+    /// Returns a [`Warning::InboxNotDeleted`] for each inbox that still could not be deleted; those stay
+    /// listed for the next attempt.
+    pub async fn cleanup(&self) -> Vec<Warning> {
+        let emails = std::mem::take(&mut *self.lock_leftover_inboxes());
+        let mut warnings = Vec::new();
+        for email in emails {
+            let inbox = self.guard_inbox(email);
+            match self.delete_inbox(inbox.email()).await {
+                Ok(()) => inbox.disarm(),
+                Err(warning) => warnings.push(warning),
+            }
+        }
+        warnings
+    }
+
+    /// Delete a guarded inbox and record the outcome in `report`. If deletion fails, the inbox is kept as a
+    /// leftover.
+    async fn clean_up(&self, inbox: InboxGuard<'_>, report: &mut GenerationReport) {
+        let result = self.delete_inbox(inbox.email()).await;
+        report.inbox_deleted = Some(result.is_ok());
+        report.cleaned_up_at = Some(SystemTime::now());
+        match result {
+            Ok(()) => inbox.disarm(),
+            Err(warning) => report.warnings.push(warning),
+        }
+    }
+
+    /// Delete the inbox of `email`, returning a warning unless the mail provider reported it as deleted.
+    async fn delete_inbox(&self, email: &str) -> std::result::Result<(), Warning> {
+        let result = self.mail_client.delete_email(email).await;
+        self.emit(ProgressEvent::InboxDeleted {
+            email: email.to_string(),
+            deleted: matches!(result, Ok(true)),
+        });
+        let reason = match result {
+            Ok(true) => return Ok(()),
+            Ok(false) => "the mail provider did not confirm the deletion".to_string(),
+            Err(e) => e.to_string(),
+        };
+        tracing::warn!(email, %reason, "inbox not deleted");
+        Err(Warning::InboxNotDeleted {
+            email: email.to_string(),
+            reason,
         })
     }
 
-    /// Wait for the MEGA confirmation email and extract the signup key.
-    async fn wait_for_confirmation(&self, email: &str) -> Result<String> {
-        let start = std::time::Instant::now();
+    /// Wait for a MEGA email carrying a `kind` link in the inbox of `email`, read through `mail`, and extract
+    /// its key.
+    ///
+    /// `deadline` is a hard bound on polling, including in-flight requests. The inbox is always polled at
+    /// least once: if `deadline` has already passed, that poll is allowed one `poll_interval`. Retries of
+    /// body fetches are reported under `stage`. Polls and the first MEGA email are counted in `report`.
+    async fn wait_for_link<N: MailProvider>(
+        &self,
+        mail: &N,
+        email: &str,
+        deadline: SystemTime,
+        kind: LinkKind,
+        stage: Stage,
+        mut report: Option<&mut GenerationReport>,
+    ) -> Result<String> {
+        let start = Instant::now();
+        let limit = start
+            + deadline
+                .duration_since(SystemTime::now())
+                .unwrap_or_default();
+        // The first poll (and any body fetches it leads to) may run for one poll interval past `deadline`
+        let first_limit = limit.max(start + self.poll_interval);
         let mut saw_mega_email = false;
+        let mut seen_ids = HashSet::new();
+        // Messages that failed validation are skipped from then on; the first one is reported if nothing
+        // valid arrives in time
+        let mut rejected_ids = HashSet::new();
+        let mut suspicious = None;
+        let mut attempt = 0;
+
+        let expired = |saw_mega_email, suspicious: Option<Error>| {
+            suspicious.unwrap_or(if saw_mega_email {
+                Error::NoConfirmationLink
+            } else {
+                Error::EmailTimeout
+            })
+        };
 
         loop {
-            if start.elapsed() >= self.timeout {
-                return if saw_mega_email {
-                    Err(Error::NoConfirmationLink)
-                } else {
-                    Err(Error::EmailTimeout)
-                };
+            if attempt > 0 && Instant::now() >= limit {
+                return Err(expired(saw_mega_email, suspicious));
             }
+            let poll_limit = if attempt == 0 { first_limit } else { limit };
+            let span = tracing::debug_span!(
+                "poll",
+                attempt = attempt + 1,
+                email,
+                messages = tracing::field::Empty
+            );
 
-            let messages = self.mail_client.get_messages(email).await?;
+            let Ok(messages) = tokio::time::timeout_at(poll_limit, mail.get_messages(email))
+                .instrument(span.clone())
+                .await
+            else {
+                return Err(expired(saw_mega_email, suspicious));
+            };
+            let messages = messages?;
+            span.record("messages", messages.len());
+            attempt += 1;
+            if let Some(report) = report.as_deref_mut() {
+                report.polls += 1;
+            }
+            self.emit(ProgressEvent::Polled {
+                attempt,
+                messages: messages.len(),
+            });
 
             // Look for MEGA confirmation email
-            for msg in &messages {
-                if msg.mail_from.contains("mega") || msg.mail_subject.contains("MEGA") {
-                    saw_mega_email = true;
+            for msg in messages.iter().filter(|msg| looks_like_mega(msg)) {
+                if rejected_ids.contains(&msg.id) {
+                    continue;
+                }
+                if !saw_mega_email && let Some(report) = report.as_deref_mut() {
+                    report.first_mega_email_at = Some(SystemTime::now());
+                }
+                saw_mega_email = true;
+                if seen_ids.insert(msg.id.clone()) {
+                    tracing::info!(
+                        parent: &span,
+                        from = %msg.from,
+                        subject = %msg.subject,
+                        "likely MEGA email"
+                    );
+                    self.emit(ProgressEvent::MegaEmailSeen {
+                        from: msg.from.clone(),
+                        subject: msg.subject.clone(),
+                    });
+                }
 
+                let reason = if is_trusted_sender(&msg.from) {
                     // Fetch full email body
-                    let details = self.mail_client.fetch_email(email, &msg.mail_id).await?;
-                    if let Some(key) = extract_confirm_key(&details.mail_body) {
-                        return Ok(key);
+                    let fetch = self.retrying(stage, || mail.fetch_body(email, &msg.id));
+                    let Ok((body, _)) = tokio::time::timeout_at(poll_limit, fetch)
+                        .instrument(span.clone())
+                        .await
+                    else {
+                        return Err(expired(saw_mega_email, suspicious));
+                    };
+                    match extract_link_key(&body?, kind) {
+                        Ok(Some(key)) => {
+                            tracing::info!(parent: &span, subject = %msg.subject, "link key extracted");
+                            self.emit(ProgressEvent::ConfirmKeyExtracted);
+                            return Ok(key);
+                        }
+                        Ok(None) => {
+                            tracing::debug!(parent: &span, subject = %msg.subject, "no link found");
+                            continue;
+                        }
+                        Err(reason) => reason,
                     }
+                } else {
+                    SuspicionReason::UntrustedSender
+                };
+
+                // Anyone can mail a temporary inbox, so a forged email must not stop the genuine one
+                tracing::warn!(parent: &span, from = %msg.from, %reason, "skipping suspicious email");
+                rejected_ids.insert(msg.id.clone());
+                if let Some(report) = report.as_deref_mut() {
+                    report.warnings.push(Warning::SuspiciousEmailSkipped {
+                        from: msg.from.clone(),
+                        reason: reason.to_string(),
+                    });
                 }
+                suspicious.get_or_insert(Error::SuspiciousEmail {
+                    from: msg.from.clone(),
+                    reason,
+                });
             }
 
-            tokio::time::sleep(self.poll_interval).await;
+            tokio::time::sleep_until(limit.min(Instant::now() + self.poll_interval)).await;
         }
     }
+
+    /// Move a confirmed account to `new_email`, whose inbox is read through `mail`.
+    ///
+    /// Asks MEGA for the change, polls the inbox of `new_email` through `mail` for MEGA's `#verify` link the
+    /// same way [`AccountGenerator::generate`] polls for the confirmation email, and completes the change with
+    /// the key it carries. The generator's own mail provider is not used, since `new_email` is normally an
+    /// address it cannot read. `mail` is attached to `new_email` with [`MailProvider::attach`] before
+    /// polling. Polling gives up `timeout` after the request. Neither the old nor the new inbox is deleted.
+    ///
+    /// When no [`MailProvider`] can read `new_email`, use [`AccountGenerator::request_email_change`] and
+    /// [`AccountGenerator::confirm_email_change`] instead.
+    ///
+    /// Returns a copy of `account` with `email` set to `new_email`.
+    ///
+    /// # Errors
+    ///
+    /// Errors are wrapped in [`Error::StageFailed`] for [`Stage::ChangingEmail`]. The root error is:
+    /// - [`Error::Mega`] if MEGA rejects the request or the key, for example because the password is wrong
+    /// - [`Error::Mail`] if attaching to or polling the new inbox or fetching a message body fails
+    /// - [`Error::EmailTimeout`] or [`Error::NoConfirmationLink`] if no usable link arrives before `timeout`
+    /// - [`Error::SuspiciousEmail`] if a likely MEGA email fails sender or link validation and no valid one
+    ///   arrives before `timeout`
+    ///
+    /// Returns [`Error::StageTimeout`] if the change exceeds its stage timeout or the total timeout.
+    pub async fn change_email<N: MailProvider>(
+        &self,
+        account: &GeneratedAccount,
+        new_email: &str,
+        mail: &N,
+    ) -> Result<GeneratedAccount> {
+        let cancel = CancellationToken::new();
+        let run = self.run_context(&cancel);
+        self.run_stage(&run, Stage::ChangingEmail, Some(&account.email), async {
+            self.request_change(account, new_email).await?;
+            mail.attach(new_email).await?;
+            let key = self
+                .wait_for_link(
+                    mail,
+                    new_email,
+                    SystemTime::now() + self.timeout,
+                    LinkKind::EmailChange,
+                    Stage::ChangingEmail,
+                    None,
+                )
+                .await?;
+            self.confirm_change(account, new_email, &key).await
+        })
+        .await
+    }
+
+    /// Permanently delete a confirmed account.
+    ///
+    /// Starts MEGA's cancellation flow, polls the inbox of `account.email` for the `#cancel` link the same
+    /// way [`AccountGenerator::generate`] polls for the confirmation email, and confirms the cancellation with
+    /// the key it carries. The inbox must still be readable through the mail provider, which is attached to
+    /// `account.email` with [`MailProvider::attach`] first, so accounts generated in another session can be
+    /// cancelled; polling gives up `timeout` after the request. The inbox is not deleted.
+    ///
+    /// # Errors
+    ///
+    /// Errors are wrapped in [`Error::StageFailed`] for [`Stage::CancellingAccount`]. The root error is:
+    /// - [`Error::Mega`] if MEGA rejects the request or the key, for example because the password is wrong
+    /// - [`Error::Mail`] if attaching to or polling the inbox or fetching a message body fails
+    /// - [`Error::EmailTimeout`] or [`Error::NoConfirmationLink`] if no usable link arrives before `timeout`
+    /// - [`Error::SuspiciousEmail`] if a likely MEGA email fails sender or link validation and no valid one
+    ///   arrives before `timeout`
+    ///
+    /// Returns [`Error::StageTimeout`] if cancellation exceeds its stage timeout or the total timeout.
+    pub async fn cancel_account(&self, account: &GeneratedAccount) -> Result<()> {
+        let password = account.password.expose_secret();
+        let cancel = CancellationToken::new();
+        let run = self.run_context(&cancel);
+        self.run_stage(
+            &run,
+            Stage::CancellingAccount,
+            Some(&account.email),
+            async {
+                self.mail_client.attach(&account.email).await?;
+                self.registrar
+                    .request_cancellation(&account.email, password)
+                    .await?;
+                self.emit(ProgressEvent::CancellationRequested {
+                    email: account.email.clone(),
+                });
+
+                let key = self
+                    .wait_for_link(
+                        &self.mail_client,
+                        &account.email,
+                        SystemTime::now() + self.timeout,
+                        LinkKind::Cancellation,
+                        Stage::CancellingAccount,
+                        None,
+                    )
+                    .await?;
+                let (result, _) = self
+                    .retrying(Stage::CancellingAccount, || {
+                        self.registrar
+                            .confirm_cancellation(&account.email, password, &key)
+                    })
+                    .await;
+                result?;
+                self.emit(ProgressEvent::AccountCancelled {
+                    email: account.email.clone(),
+                });
+                Ok(())
+            },
+        )
+        .await
+    }
+}
+
+impl<M, R: Registrar> AccountGenerator<M, R> {
+    /// Run [`Stage::Verifying`] for `pending` with `confirm_key`.
+    ///
+    /// Running out of time, including during a retry backoff, is reported as [`Error::VerificationFailed`]
+    /// wrapping the [`Error::StageTimeout`], so the caller keeps the key just as when retries run out.
+    async fn verify_stage(
+        &self,
+        run: &RunContext<'_>,
+        pending: &PendingRegistration,
+        confirm_key: &str,
+    ) -> Result<()> {
+        let attempts = AtomicU32::new(0);
+        let result = self
+            .run_stage(
+                run,
+                Stage::Verifying,
+                Some(&pending.email),
+                self.verify_with_retries(pending, confirm_key, &attempts),
+            )
+            .await;
+        match result {
+            Err(timeout @ Error::StageTimeout { .. }) => Err(Error::StageFailed {
+                stage: Stage::Verifying,
+                email: Some(pending.email.clone()),
+                source: Box::new(verification_failed(
+                    pending,
+                    confirm_key,
+                    attempts.load(Ordering::Relaxed),
+                    timeout,
+                )),
+            }),
+            result => result,
+        }
+    }
+
+    /// Verify `pending` with `confirm_key`, retrying transient failures and counting calls in `attempts`.
+    ///
+    /// Any failure is reported as [`Error::VerificationFailed`] so the caller keeps the key.
+    async fn verify_with_retries(
+        &self,
+        pending: &PendingRegistration,
+        confirm_key: &str,
+        attempts: &AtomicU32,
+    ) -> Result<()> {
+        let (result, attempts) = self
+            .retrying(Stage::Verifying, || {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                self.registrar.verify(&pending.state, confirm_key)
+            })
+            .await;
+        result.map_err(|source| verification_failed(pending, confirm_key, attempts, source))
+    }
+
+    /// Submit a MEGA registration for an address the caller controls, without creating a temporary inbox.
+    ///
+    /// The confirmation email is delivered to `email` and never read by the generator. Pass the link (or the
+    /// key it carries) to [`AccountGenerator::confirm_manually`] together with the returned
+    /// [`PendingRegistration`]. Do not pass the result to [`AccountGenerator::resume`], which polls and then
+    /// deletes the inbox through the mail provider.
+    ///
+    /// Uses `name` as the display name, or a random one when `None`. The returned deadline is now plus
+    /// `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WeakPassword`] if `password` fails the password policy, [`Error::Mega`] if
+    /// registration fails and [`Error::StageTimeout`] if it runs out of time.
+    pub async fn register_existing(
+        &self,
+        email: &str,
+        password: &str,
+        name: Option<&str>,
+    ) -> Result<PendingRegistration> {
+        self.check_password(password)?;
+        let name = name.map_or_else(|| self.random_name(), str::to_string);
+        let cancel = CancellationToken::new();
+        let state = self
+            .run_stage(
+                &self.run_context(&cancel),
+                Stage::Registering,
+                Some(email),
+                self.registrar.register(email, password, &name),
+            )
+            .await?;
+        self.emit(ProgressEvent::RegistrationSubmitted {
+            email: email.to_string(),
+        });
+
+        Ok(PendingRegistration {
+            email: email.to_string(),
+            name,
+            state,
+            deadline: SystemTime::now() + self.timeout,
+        })
+    }
+
+    /// Verify a registration with a confirmation link or key supplied by the caller.
+    ///
+    /// `confirmation` may be the full link from MEGA's email (`https://mega.nz/#confirm<KEY>`), the
+    /// `#confirm<KEY>` fragment, or the bare key. Links are subject to the same host, scheme and key checks
+    /// as links read from a temporary inbox. No inbox is polled or deleted, and `pending.deadline` is not
+    /// enforced.
+    ///
+    /// # Errors
+    ///
+    /// Returns:
+    /// - [`Error::NoConfirmationLink`] if `confirmation` is empty or a link without a confirmation key
+    /// - [`Error::InvalidConfirmationLink`] if the link points outside MEGA, is not `https`, or carries a
+    ///   malformed key
+    /// - [`Error::VerificationFailed`] if MEGA rejects the key, verification still fails after retries, or
+    ///   verification runs out of time
+    pub async fn confirm_manually(
+        &self,
+        pending: &PendingRegistration,
+        password: &str,
+        confirmation: &str,
+    ) -> Result<GeneratedAccount> {
+        let confirm_key = parse_manual_link(confirmation, LinkKind::Signup)
+            .map_err(Error::InvalidConfirmationLink)?
+            .ok_or(Error::NoConfirmationLink)?;
+        self.emit(ProgressEvent::ConfirmKeyExtracted);
+
+        let started_at = SystemTime::now();
+        let cancel = CancellationToken::new();
+        let run = self.run_context(&cancel);
+        self.verify_stage(&run, pending, &confirm_key).await?;
+        let verified_at = SystemTime::now();
+        self.emit(ProgressEvent::Verified {
+            email: pending.email.clone(),
+        });
+
+        let mut report = GenerationReport {
+            started_at: Some(started_at),
+            verified_at: Some(verified_at),
+            ..GenerationReport::default()
+        };
+        let mut account = self
+            .finish_account(pending, password, &run, &mut report)
+            .await;
+        account.report = report;
+        Ok(account)
+    }
+
+    /// Ask MEGA to move a confirmed account to `new_email`, an address the caller reads themselves.
+    ///
+    /// MEGA sends a link to `new_email`; pass it to [`AccountGenerator::confirm_email_change`] to complete the
+    /// change. Until then the account keeps its current address.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Mega`] if MEGA rejects the request, wrapped in [`Error::StageFailed`] for
+    /// [`Stage::ChangingEmail`], and [`Error::StageTimeout`] if the request runs out of time.
+    pub async fn request_email_change(
+        &self,
+        account: &GeneratedAccount,
+        new_email: &str,
+    ) -> Result<()> {
+        let cancel = CancellationToken::new();
+        self.run_stage(
+            &self.run_context(&cancel),
+            Stage::ChangingEmail,
+            Some(&account.email),
+            self.request_change(account, new_email),
+        )
+        .await
+    }
+
+    /// Complete an email change with the link or key MEGA sent to `new_email`.
+    ///
+    /// `link` may be the full link (`https://mega.nz/#verify<KEY>`), the `#verify<KEY>` fragment, or the bare
+    /// key, and is checked like the link passed to [`AccountGenerator::confirm_manually`]. Returns a copy of
+    /// `account` with `email` set to `new_email`.
+    ///
+    /// # Errors
+    ///
+    /// Returns:
+    /// - [`Error::NoConfirmationLink`] if `link` is empty or a link without a key
+    /// - [`Error::InvalidConfirmationLink`] if the link points outside MEGA, is not `https`, or carries a
+    ///   malformed key
+    /// - [`Error::Mega`] if MEGA rejects the key, wrapped in [`Error::StageFailed`] for
+    ///   [`Stage::ChangingEmail`]
+    /// - [`Error::StageTimeout`] if the confirmation runs out of time
+    pub async fn confirm_email_change(
+        &self,
+        account: &GeneratedAccount,
+        new_email: &str,
+        link: &str,
+    ) -> Result<GeneratedAccount> {
+        let key = parse_manual_link(link, LinkKind::EmailChange)
+            .map_err(Error::InvalidConfirmationLink)?
+            .ok_or(Error::NoConfirmationLink)?;
+        self.emit(ProgressEvent::ConfirmKeyExtracted);
+
+        let cancel = CancellationToken::new();
+        self.run_stage(
+            &self.run_context(&cancel),
+            Stage::ChangingEmail,
+            Some(&account.email),
+            self.confirm_change(account, new_email, &key),
+        )
+        .await
+    }
+
+    async fn request_change(&self, account: &GeneratedAccount, new_email: &str) -> Result<()> {
+        self.registrar
+            .request_email_change(&account.email, account.password.expose_secret(), new_email)
+            .await?;
+        self.emit(ProgressEvent::EmailChangeRequested {
+            email: account.email.clone(),
+            new_email: new_email.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Confirm the change of `account` to `new_email` with `key`, retrying transient failures.
+    async fn confirm_change(
+        &self,
+        account: &GeneratedAccount,
+        new_email: &str,
+        key: &str,
+    ) -> Result<GeneratedAccount> {
+        let (result, _) = self
+            .retrying(Stage::ChangingEmail, || {
+                self.registrar.confirm_email_change(
+                    &account.email,
+                    account.password.expose_secret(),
+                    new_email,
+                    key,
+                )
+            })
+            .await;
+        result?;
+        self.emit(ProgressEvent::EmailChanged {
+            old_email: account.email.clone(),
+            email: new_email.to_string(),
+        });
+
+        let mut account = account.clone();
+        account.email = new_email.to_string();
+        Ok(account)
+    }
+
+    /// Build the result for a verified registration, logging in and exporting the recovery key if enabled.
+    ///
+    /// The account is confirmed by now, so it is always returned: failures of the optional steps are recorded
+    /// as warnings in `report` instead.
+    async fn finish_account(
+        &self,
+        pending: &PendingRegistration,
+        password: &str,
+        run: &RunContext<'_>,
+        report: &mut GenerationReport,
+    ) -> GeneratedAccount {
+        let mut account = account_from(pending, password);
+        if self.login_check {
+            let session = self
+                .run_stage(
+                    run,
+                    Stage::LoggingIn,
+                    Some(&pending.email),
+                    self.registrar.login(&pending.email, password),
+                )
+                .await;
+            match session {
+                Ok(session) => {
+                    self.emit(ProgressEvent::LoggedIn {
+                        email: pending.email.clone(),
+                        storage_total: session.storage_total,
+                        storage_used: session.storage_used,
+                    });
+                    account.session = Some(session);
+                }
+                Err(e) => report.warnings.push(Warning::LoginFailed {
+                    email: pending.email.clone(),
+                    reason: e.to_string(),
+                }),
+            }
+        }
+        if self.export_recovery_key {
+            let recovery_key = self
+                .run_stage(
+                    run,
+                    Stage::ExportingRecoveryKey,
+                    Some(&pending.email),
+                    self.registrar.recovery_key(&pending.email, password),
+                )
+                .await;
+            match recovery_key {
+                Ok(recovery_key) => {
+                    self.emit(ProgressEvent::RecoveryKeyExported {
+                        email: pending.email.clone(),
+                    });
+                    account.recovery_key = Some(recovery_key);
+                }
+                Err(e) => report.warnings.push(Warning::RecoveryKeyNotExported {
+                    email: pending.email.clone(),
+                    reason: e.to_string(),
+                }),
+            }
+        }
+        account
+    }
+}
+
+impl<M, R> AccountGenerator<M, R> {
+    fn lock_rng(&self) -> MutexGuard<'_, StdRng> {
+        self.rng.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_leftover_inboxes(&self) -> MutexGuard<'_, Vec<String>> {
+        self.leftover_inboxes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn guard_inbox(&self, email: String) -> InboxGuard<'_> {
+        InboxGuard {
+            email: Some(email),
+            leftover_inboxes: &self.leftover_inboxes,
+        }
+    }
+
+    fn random_name(&self) -> String {
+        generate_random_name(&mut *self.lock_rng())
+    }
+
+    fn check_password(&self, password: &str) -> Result<()> {
+        self.password_policy
+            .check(password)
+            .map_err(Error::WeakPassword)
+    }
+
+    fn emit(&self, event: ProgressEvent) {
+        if let Some(progress) = &self.progress {
+            progress.emit(event);
+        }
+    }
+
+    /// Run `op`, repeating it with exponential backoff while it fails with a retryable error.
+    ///
+    /// Returns the last result together with the number of attempts made.
+    async fn retrying<T, Fut>(&self, stage: Stage, mut op: impl FnMut() -> Fut) -> (Result<T>, u32)
+    where
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempts = 1;
+        let mut delay = self.retry_backoff;
+        loop {
+            match op().await {
+                Err(e) if attempts <= self.max_retries && e.is_retryable() => {
+                    tracing::warn!(
+                        %stage,
+                        attempt = attempts,
+                        delay_ms = delay.as_millis() as u64,
+                        error = %e,
+                        "retrying"
+                    );
+                    self.emit(ProgressEvent::RetryScheduled {
+                        stage,
+                        attempt: attempts,
+                        delay,
+                    });
+                    tokio::time::sleep(delay).await;
+                    delay = delay.saturating_mul(2);
+                    attempts += 1;
+                }
+                result => return (result, attempts),
+            }
+        }
+    }
+
+    /// Start the clock for a public call, observing `cancel`.
+    fn run_context<'a>(&self, cancel: &'a CancellationToken) -> RunContext<'a> {
+        RunContext {
+            cancel,
+            deadline: self.total_timeout.map(|timeout| Instant::now() + timeout),
+        }
+    }
+
+    /// Run `fut` as `stage`, bounded by cancellation, the stage's timeout and the total timeout.
+    ///
+    /// Returns [`Error::Cancelled`] or [`Error::StageTimeout`] naming `stage` if `fut` does not finish first,
+    /// and wraps any error from `fut` in [`Error::StageFailed`].
+    async fn run_stage<T>(
+        &self,
+        run: &RunContext<'_>,
+        stage: Stage,
+        email: Option<&str>,
+        fut: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let stage_deadline = self
+            .stage_timeouts
+            .get(&stage)
+            .map(|timeout| Instant::now() + *timeout);
+        let limit = match (stage_deadline, run.deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let expired = async {
+            match limit {
+                Some(limit) => tokio::time::sleep_until(limit).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let span = tracing::info_span!("stage", %stage, email);
+        let result = tokio::select! {
+            biased;
+            _ = run.cancel.cancelled() => Err(Error::Cancelled {
+                stage,
+                email: email.map(str::to_string),
+            }),
+            result = fut.instrument(span.clone()) => result.map_err(|source| Error::StageFailed {
+                stage,
+                email: email.map(str::to_string),
+                source: Box::new(source),
+            }),
+            _ = expired => Err(Error::StageTimeout {
+                stage,
+                email: email.map(str::to_string),
+            }),
+        };
+        match &result {
+            Ok(_) => tracing::debug!(parent: &span, "stage finished"),
+            Err(Error::Cancelled { .. }) => tracing::info!(parent: &span, "stage cancelled"),
+            Err(e) => tracing::warn!(parent: &span, error = %e, "stage failed"),
+        }
+        result
+    }
 }
 
 impl Default for AccountGeneratorBuilder {
@@ -165,20 +1180,36 @@ impl Default for AccountGeneratorBuilder {
         Self {
             timeout: Duration::from_secs(300), // 5 minute timeout
             poll_interval: Duration::from_secs(5),
+            total_timeout: None,
+            stage_timeouts: HashMap::new(),
+            max_retries: 3,
+            retry_backoff: Duration::from_secs(1),
+            login_check: false,
+            export_recovery_key: false,
+            password_policy: PasswordPolicy::default(),
+            rng_seed: None,
             proxy: None,
+            progress: None,
         }
     }
 }
 
 impl AccountGeneratorBuilder {
-    /// Configure an HTTP proxy URL for MEGA and GuerrillaMail requests.
+    /// Route MEGA and GuerrillaMail requests through `proxy`.
     ///
-    /// The value is forwarded directly to both underlying clients.
+    /// The proxy is validated when it is parsed, so a malformed URL is reported as [`Error::InvalidProxy`]
+    /// before any request is made:
     ///
-    /// This crate does not validate the URL beyond passing it through to the clients; invalid values are
-    /// typically reported as [`Error::Mail`] during [`AccountGeneratorBuilder::build`].
-    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
-        self.proxy = Some(proxy.into());
+    /// ```
+    /// # fn main() -> meganz_account_generator::Result<()> {
+    /// use meganz_account_generator::AccountGenerator;
+    ///
+    /// let builder = AccountGenerator::builder().proxy("socks5://127.0.0.1:1080".parse()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
         self
     }
 
@@ -188,6 +1219,8 @@ impl AccountGeneratorBuilder {
     /// - [`Error::EmailTimeout`] if no likely MEGA email has been observed
     /// - [`Error::NoConfirmationLink`] if a likely MEGA email was observed, but no confirmation key could be
     ///   extracted from its body
+    ///
+    /// In-flight inbox requests are abandoned when the timeout is reached.
     pub fn timeout(mut self, timeout: Duration) -> Self {
         self.timeout = timeout;
         self
@@ -199,6 +1232,99 @@ impl AccountGeneratorBuilder {
         self
     }
 
+    /// Configure a hard limit on the wall-clock time of each generator call.
+    ///
+    /// The clock starts when `generate`, `start_registration`, `resume` or one of their variants is called
+    /// and covers every stage, including in-flight requests. When it runs out, the call fails with
+    /// [`Error::StageTimeout`] naming the stage that was running, or with [`Error::VerificationFailed`] carrying
+    /// the confirmation key if it runs out during [`Stage::Verifying`]. Deletion of the temporary inbox is still
+    /// attempted after the limit has passed.
+    pub fn total_timeout(mut self, timeout: Duration) -> Self {
+        self.total_timeout = Some(timeout);
+        self
+    }
+
+    /// Configure a hard limit on the time a single [`Stage`] may take.
+    ///
+    /// When it runs out, the call fails with [`Error::StageTimeout`] naming `stage`; for [`Stage::Verifying`],
+    /// including retry backoff, that error is wrapped in [`Error::VerificationFailed`] so the confirmation key
+    /// is not lost. This is independent of
+    /// [`AccountGeneratorBuilder::timeout`], which bounds the wait for the confirmation email and reports
+    /// [`Error::EmailTimeout`] or [`Error::NoConfirmationLink`] instead. Replaces any timeout previously set
+    /// for the same stage.
+    pub fn stage_timeout(mut self, stage: Stage, timeout: Duration) -> Self {
+        self.stage_timeouts.insert(stage, timeout);
+        self
+    }
+
+    /// Configure how many times a failed verification or message-body fetch is retried.
+    ///
+    /// Only errors for which [`Error::is_retryable`] returns `true` are retried; registration and inbox
+    /// creation are never repeated, so a retry cannot create a second account. Set to `0` to disable retries.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Configure the delay before the first retry. The delay doubles after each further retry.
+    pub fn retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Log in to each account after it is confirmed, proving that the credentials work.
+    ///
+    /// When enabled, the returned [`GeneratedAccount::session`] holds a serialized MEGA session and the
+    /// account's storage quota. The account is already confirmed when the login runs, so a failed login does
+    /// not fail the call: the account is returned without a session and with a [`Warning::LoginFailed`] in
+    /// its [`GeneratedAccount::report`].
+    pub fn login_check(mut self, enabled: bool) -> Self {
+        self.login_check = enabled;
+        self
+    }
+
+    /// Export each account's recovery key after it is confirmed.
+    ///
+    /// When enabled, the returned [`GeneratedAccount::recovery_key`] holds the account's master key, which
+    /// MEGA accepts to reset a lost password without losing files. Exporting requires a login, independent
+    /// of [`AccountGeneratorBuilder::login_check`]. As with the login check, a failed export does not fail the
+    /// call: the account is returned without a recovery key and with a [`Warning::RecoveryKeyNotExported`] in
+    /// its [`GeneratedAccount::report`].
+    pub fn export_recovery_key(mut self, enabled: bool) -> Self {
+        self.export_recovery_key = enabled;
+        self
+    }
+
+    /// Configure the password strength requirements checked before registering.
+    ///
+    /// [`AccountGenerator::generate`], [`AccountGenerator::start_registration`] and
+    /// [`AccountGenerator::register_existing`] fail with [`Error::WeakPassword`] before any network request
+    /// when the password does not meet `policy`. Use [`PasswordPolicy::permissive`] to disable the check.
+    pub fn password_policy(mut self, policy: PasswordPolicy) -> Self {
+        self.password_policy = policy;
+        self
+    }
+
+    /// Seed the random source of temporary aliases and display names.
+    ///
+    /// Generators built with the same seed use the same sequence of aliases and random names, which makes
+    /// runs against the `test-util` fakes reproducible. Passwords from
+    /// [`crate::PasswordGenerator`] are not affected. Leave unset outside tests: a seeded alias is
+    /// predictable, and anyone who can predict it can read the inbox.
+    pub fn rng_seed(mut self, seed: u64) -> Self {
+        self.rng_seed = Some(seed);
+        self
+    }
+
+    /// Register a handler that receives a [`ProgressEvent`] as each stage of generation completes.
+    ///
+    /// The handler runs synchronously on the task driving `generate`, so it should return quickly; forward
+    /// events to a channel if they need slow processing. Replaces any previously registered handler.
+    pub fn on_progress(mut self, handler: impl Fn(&ProgressEvent) + Send + Sync + 'static) -> Self {
+        self.progress = Some(ProgressHandler::new(handler));
+        self
+    }
+
     /// Build an [`AccountGenerator`] with the configured values.
     ///
     /// # Errors
@@ -206,45 +1332,77 @@ impl AccountGeneratorBuilder {
     /// Returns [`Error::Mail`] if the GuerrillaMail client fails to initialize
     /// (e.g., proxy misconfiguration or network errors).
     pub async fn build(self) -> Result<AccountGenerator> {
-        let mail_client = build_mail_client(self.proxy.as_deref()).await?;
-        Ok(AccountGenerator {
+        let mail_client = build_mail_client(self.proxy.as_ref()).await?;
+        let registrar = MegaRegistrar::new(self.proxy.clone());
+        Ok(self.build_with(mail_client, registrar))
+    }
+
+    /// Build an [`AccountGenerator`] for addresses the caller controls.
+    ///
+    /// No temporary mail client is created, so only [`AccountGenerator::register_existing`],
+    /// [`AccountGenerator::confirm_manually`], [`AccountGenerator::request_email_change`] and
+    /// [`AccountGenerator::confirm_email_change`] are available. The configured `proxy` is used for MEGA requests.
+    pub fn build_manual(self) -> AccountGenerator<(), MegaRegistrar> {
+        let registrar = MegaRegistrar::new(self.proxy.clone());
+        self.build_with((), registrar)
+    }
+
+    /// Build an [`AccountGenerator`] that uses the given mail provider and registrar.
+    ///
+    /// No network requests are made and the configured `proxy` is ignored; the caller is responsible for
+    /// configuring `mail_client` and `registrar`.
+    pub fn build_with<M, R>(self, mail_client: M, registrar: R) -> AccountGenerator<M, R> {
+        AccountGenerator {
             mail_client,
+            registrar,
             timeout: self.timeout,
             poll_interval: self.poll_interval,
-            proxy: self.proxy,
-        })
+            total_timeout: self.total_timeout,
+            stage_timeouts: self.stage_timeouts,
+            max_retries: self.max_retries,
+            retry_backoff: self.retry_backoff,
+            login_check: self.login_check,
+            export_recovery_key: self.export_recovery_key,
+            password_policy: self.password_policy,
+            rng: Mutex::new(match self.rng_seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            }),
+            leftover_inboxes: Mutex::new(Vec::new()),
+            progress: self.progress,
+        }
     }
 }
 
-async fn build_mail_client(proxy: Option<&str>) -> Result<MailClient> {
-    let mut builder = MailClient::builder();
-    if let Some(proxy_url) = proxy {
-        builder = builder.proxy(proxy_url);
+fn account_from(pending: &PendingRegistration, password: &str) -> GeneratedAccount {
+    GeneratedAccount {
+        email: pending.email.clone(),
+        password: SecretString::from(password),
+        name: pending.name.clone(),
+        session: None,
+        recovery_key: None,
+        report: GenerationReport::default(),
     }
-    builder.build().await.map_err(Into::into)
 }
 
-/// Extract the confirmation key from a MEGA email body.
-fn extract_confirm_key(body: &str) -> Option<String> {
-    // MEGA confirmation links look like:
-    // https://mega.nz/#confirm<KEY>
-    // https://mega.nz/confirm<KEY>
-
-    let valid_patterns = [
-        r"https://mega\.nz/#confirm([a-zA-Z0-9_-]+)",
-        r"https://mega\.nz/confirm([a-zA-Z0-9_-]+)",
-        r#"href="https://mega\.nz/#confirm([^"]+)"#,
-        r#"href="https://mega\.nz/confirm([^"]+)"#,
-    ];
-
-    for pattern in &valid_patterns {
-        if let Ok(re) = Regex::new(pattern) {
-            if let Some(caps) = re.captures(body) {
-                if let Some(key) = caps.get(1) {
-                    return Some(key.as_str().to_string());
-                }
-            }
-        }
+fn verification_failed(
+    pending: &PendingRegistration,
+    confirm_key: &str,
+    attempts: u32,
+    source: Error,
+) -> Error {
+    Error::VerificationFailed {
+        pending: Box::new(pending.clone()),
+        confirm_key: SecretString::from(confirm_key),
+        attempts,
+        source: Box::new(source),
+    }
+}
+
+async fn build_mail_client(proxy: Option<&ProxyConfig>) -> Result<MailClient> {
+    let mut builder = MailClient::builder();
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(proxy.url());
     }
-    None
+    builder.build().await.map_err(Into::into)
 }
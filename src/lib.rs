@@ -6,7 +6,8 @@
 //! 3. Poll the inbox for a likely MEGA confirmation email.
 //! 4. Extract the confirmation key from the email body and verify the registration.
 //!
-//! The returned [`GeneratedAccount`] is only produced after confirmation succeeds.
+//! The returned [`GeneratedAccount`] is only produced after confirmation succeeds. Use a
+//! [`PasswordGenerator`] to give every account its own random password.
 //!
 //! # Add To `Cargo.toml`
 //!
@@ -15,6 +16,10 @@
 //! meganz-account-generator = "0.4"
 //! ```
 //!
+//! Enable the `blocking` feature for `blocking::BlockingAccountGenerator`, which runs the generator on its
+//! own runtime for synchronous callers, and the `test-util` feature (normally in `[dev-dependencies]`) for
+//! the in-memory fakes described under [Offline Testing](#offline-testing).
+//!
 //! # Example
 //!
 //! ```no_run
@@ -25,7 +30,7 @@
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     let generator = AccountGenerator::builder()
 //!         // Optional: route both MEGA and GuerrillaMail traffic through a proxy.
-//!         // .proxy("http://127.0.0.1:8080")
+//!         // .proxy("http://127.0.0.1:8080".parse()?)
 //!         .timeout(Duration::from_secs(180))
 //!         .poll_interval(Duration::from_secs(3))
 //!         .build()
@@ -36,15 +41,95 @@
 //!         .await?;
 //!
 //!     println!("Created account: {}", account.email);
+//!     println!("Took {:?}", account.report.duration());
 //!     Ok(())
 //! }
 //! ```
 //!
+//! Every [`GeneratedAccount`] carries a [`GenerationReport`] with the time each step of its run finished,
+//! the number of inbox polls and whether the temporary inbox was deleted.
+//!
+//! # Resuming Registrations
+//!
+//! [`AccountGenerator::start_registration`] stops after MEGA accepts the registration and returns a
+//! [`PendingRegistration`]. Save it with [`PendingRegistration::save`] and pass it to
+//! [`AccountGenerator::resume`] later, even after a restart, to poll for the confirmation email and verify.
+//! A resume that is cancelled, times out or fails before the deadline keeps the inbox, so it can be resumed
+//! again.
+//!
+//! To register an address you control, build with [`AccountGeneratorBuilder::build_manual`], call
+//! [`AccountGenerator::register_existing`], and pass the link from MEGA's email to
+//! [`AccountGenerator::confirm_manually`].
+//!
+//! # After Confirmation
+//!
+//! Enable [`AccountGeneratorBuilder::login_check`] to log in to each account after it is confirmed. The
+//! returned [`GeneratedAccount::session`] then holds a serialized MEGA session and the storage quota.
+//! Enable [`AccountGeneratorBuilder::export_recovery_key`] to also return the account's recovery key in
+//! [`GeneratedAccount::recovery_key`]; [`vault::Vault`] stores it alongside the password.
+//!
+//! Temporary inboxes are public, so anyone who knows the alias can request a password reset. Move an account
+//! to an address you control with [`AccountGenerator::change_email`], which polls the new address for MEGA's
+//! verification link through a [`MailProvider`] you pass in for it, or with
+//! [`AccountGenerator::request_email_change`] and [`AccountGenerator::confirm_email_change`] when you read
+//! that mail yourself.
+//!
+//! [`AccountGenerator::cancel_account`] permanently deletes an account, reading MEGA's cancellation link
+//! from the account's inbox the same way.
+//!
+//! # Offline Testing
+//!
+//! The generator is generic over a [`MailProvider`] and a [`Registrar`]. With the `test-util` feature,
+//! `FakeMailProvider` and `FakeRegistrar` implement both sides in memory, so the full register, poll, extract
+//! and verify path can run without network access. [`AccountGeneratorBuilder::rng_seed`] pins the aliases and
+//! display names a run uses:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! meganz-account-generator = { version = "0.4", features = ["test-util"] }
+//! ```
+//!
+//! ```
+//! use std::time::Duration;
+//! use meganz_account_generator::{AccountGenerator, FakeMailProvider, FakeRegistrar};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> meganz_account_generator::Result<()> {
+//! let mail = FakeMailProvider::new();
+//! let registrar = FakeRegistrar::new(mail.clone());
+//! let generator = AccountGenerator::builder()
+//!     .poll_interval(Duration::from_millis(10))
+//!     .build_with(mail.clone(), registrar.clone());
+//!
+//! let account = generator.generate("S3cure-Password!").await?;
+//! assert_eq!(registrar.verified(), vec![account.email.clone()]);
+//! assert_eq!(mail.deleted(), vec![account.email]);
+//! # Ok(())
+//! # }
+//! ```
+//!
 //! # Behavior Notes
 //!
 //! - Confirmation email detection is heuristic: a message is treated as "likely MEGA" when the sender
-//!   contains `"mega"` or the subject contains `"MEGA"`.
-//! - If the inbox is cleaned up, it is best-effort: deletion errors are ignored after successful confirmation.
+//!   contains `"mega"` (case-insensitive) or the subject contains `"MEGA"`.
+//! - Likely MEGA messages are then validated strictly: the sender must be an address at `mega.nz`, `mega.io`
+//!   or `mega.co.nz`, and every confirmation link in the body must be an `https` URL on a MEGA host. Temporary
+//!   inboxes can receive mail from anyone who knows the alias, so a message that fails either check is skipped
+//!   and recorded as a [`Warning`] while polling goes on. Generation fails with [`Error::SuspiciousEmail`] only
+//!   if no valid confirmation email arrives before the timeout.
+//! - Message bodies are decoded before links are extracted: quoted-printable and base64 transfer encodings,
+//!   `multipart/*` messages and HTML entities such as `&amp;` are handled, and confirmation links wrapped in
+//!   tracking redirects are unwrapped. The unwrapped link is validated, not the redirect.
+//! - The temporary inbox is deleted on every exit path of a generation, including failures, and when a
+//!   resumed registration is finished with (see [`AccountGenerator::resume`]). Deletion errors never fail a run:
+//!   they are returned as [`Warning`]s in [`GenerationReport::warnings`], and the inbox is kept in
+//!   [`AccountGenerator::leftover_inboxes`] until [`AccountGenerator::cleanup`] deletes it. A run whose future is
+//!   dropped leaves its inbox there too.
+//! - Each stage runs inside a `stage` [`tracing`](https://docs.rs/tracing) span and each inbox check inside a
+//!   `poll` span, with the alias, message count and matched subjects as fields. Retries and failures are
+//!   logged as events. Passwords, confirmation keys, sessions and recovery keys are never recorded, and request
+//!   URLs, which can carry session IDs, are removed from errors. `megalib` itself logs full request URLs at
+//!   `trace` level, so do not enable `trace` for it where logs are kept.
 //!
 //! # Errors And Timeout Semantics
 //!
@@ -55,23 +140,68 @@
 //! - [`Error::EmailTimeout`]: no likely MEGA email was observed before `timeout` elapsed
 //! - [`Error::NoConfirmationLink`]: a likely MEGA email was observed before `timeout`, but no confirmation key
 //!   could be extracted from its body
-//!
-//! Polling waits `poll_interval` between inbox checks until the `timeout` elapses. The timeout is evaluated at
-//! the start of each poll iteration, so total wall-clock time may exceed `timeout` by the duration of an
-//! in-flight poll request plus up to one `poll_interval` sleep.
+//! - [`Error::SuspiciousEmail`]: a likely MEGA email failed sender or confirmation-link validation, and no
+//!   valid one followed before the timeout
+//! - [`Error::WeakPassword`]: the password failed the [`PasswordPolicy`] configured with
+//!   [`AccountGeneratorBuilder::password_policy`]; checked before any network request
+//! - [`Error::InvalidProxy`]: a proxy URL passed to [`ProxyConfig::parse`] has an unsupported scheme, no host,
+//!   or malformed credentials
+//! - [`Error::InvalidConfirmationLink`]: a link passed to [`AccountGenerator::confirm_manually`] failed
+//!   confirmation-link validation
+//! - [`Error::VerificationFailed`]: verification still failed after retries or ran out of time; the error
+//!   carries the extracted confirmation key and the pending registration so verification can be retried later
+//! - [`Error::Cancelled`]: a [`CancellationToken`] passed to one of the `*_cancellable` methods fired; the
+//!   error names the [`Stage`] that was running, and the temporary inbox of a generation is deleted before it is
+//!   returned
+//! - [`Error::StageTimeout`]: a stage exceeded its
+//!   [`stage_timeout`](AccountGeneratorBuilder::stage_timeout), or the call exceeded
+//!   [`total_timeout`](AccountGeneratorBuilder::total_timeout); the error names the [`Stage`] that was running
+//!
+//! Errors raised while a [`Stage`] runs are wrapped in [`Error::StageFailed`] together with the stage and the
+//! address being registered. [`Error::root`] returns the underlying error, [`Error::kind`] classifies it
+//! (including specific MEGA API error codes), [`Error::is_retryable`] says whether trying again may help, and
+//! [`Error::leaves_unconfirmed_account`] says whether MEGA had already accepted the registration.
+//!
+//! Polling waits `poll_interval` between inbox checks until the `timeout` elapses. All timeouts are hard
+//! bounds: in-flight requests are abandoned when they are reached, rather than checked between requests.
 //!
 //! # External Failures
 //!
 //! This crate depends on external services (GuerrillaMail and MEGA). Failures such as network issues, DNS/TLS
-//! errors, proxy misconfiguration, service outages, or API changes surface as [`Error::Mail`] or [`Error::Mega`].
+//! errors, unreachable proxies, service outages, or API changes surface as [`Error::Mail`] or [`Error::Mega`].
 //! Email delivery is also not guaranteed; confirmation messages may be delayed, filtered, or never arrive, which
 //! results in [`Error::EmailTimeout`] or [`Error::NoConfirmationLink`] depending on what was observed while polling.
 
 mod account;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod confirm;
 mod errors;
+pub mod export;
+#[cfg(feature = "test-util")]
+mod fake;
 mod generator;
+mod mail;
+mod mime;
+mod password;
+mod pending;
+mod progress;
+mod proxy;
 mod random;
+mod registrar;
+pub mod vault;
 
-pub use account::GeneratedAccount;
-pub use errors::{Error, Result};
+pub use account::{AccountSession, GeneratedAccount, GenerationReport};
+pub use errors::{Error, ErrorKind, Result, Stage, SuspicionReason, Warning};
+#[cfg(feature = "test-util")]
+pub use fake::{FAKE_MEGA_SENDER, FakeMailProvider, FakeRegistrar};
 pub use generator::{AccountGenerator, AccountGeneratorBuilder};
+pub use mail::{InboxMessage, MailProvider};
+pub use megalib::RegistrationState;
+pub use password::{PasswordGenerator, PasswordPolicy, PasswordWeakness, estimate_entropy};
+pub use pending::PendingRegistration;
+pub use progress::ProgressEvent;
+pub use proxy::{ProxyConfig, ProxyScheme};
+pub use registrar::{MegaRegistrar, Registrar};
+pub use secrecy::{ExposeSecret, SecretString};
+pub use tokio_util::sync::CancellationToken;
@@ -0,0 +1,71 @@
+use crate::errors::Result;
+use guerrillamail_client::Client as MailClient;
+use std::future::Future;
+
+/// Summary of a message sitting in a temporary inbox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InboxMessage {
+    /// Provider-specific message ID, passed back to [`MailProvider::fetch_body`].
+    pub id: String,
+    /// Sender address as reported by the provider.
+    pub from: String,
+    /// Subject line.
+    pub subject: String,
+}
+
+/// Temporary inbox backend used by [`crate::AccountGenerator`].
+///
+/// The default implementation is [`guerrillamail_client::Client`]. Tests can use `FakeMailProvider`, behind
+/// the `test-util` feature, to run the generator without network access.
+pub trait MailProvider {
+    /// Create a temporary address for `alias` and return the full email address.
+    fn create_email(&self, alias: &str) -> impl Future<Output = Result<String>> + Send;
+
+    /// Point the session back at the existing inbox for `email`, so later reads see its messages.
+    ///
+    /// Called before polling an inbox this session may not have created, such as one saved in a
+    /// [`crate::PendingRegistration`] or a [`crate::GeneratedAccount`].
+    fn attach(&self, email: &str) -> impl Future<Output = Result<()>> + Send;
+
+    /// List the messages currently in the inbox for `email`.
+    fn get_messages(&self, email: &str) -> impl Future<Output = Result<Vec<InboxMessage>>> + Send;
+
+    /// Fetch the full body of message `id` in the inbox for `email`.
+    fn fetch_body(&self, email: &str, id: &str) -> impl Future<Output = Result<String>> + Send;
+
+    /// Delete the inbox for `email`.
+    fn delete_email(&self, email: &str) -> impl Future<Output = Result<bool>> + Send;
+}
+
+impl MailProvider for MailClient {
+    async fn create_email(&self, alias: &str) -> Result<String> {
+        Ok(MailClient::create_email(self, alias).await?)
+    }
+
+    async fn attach(&self, email: &str) -> Result<()> {
+        // GuerrillaMail serves the address set on the session, whatever alias a request names
+        let alias = email.split('@').next().unwrap_or(email);
+        MailClient::create_email(self, alias).await?;
+        Ok(())
+    }
+
+    async fn get_messages(&self, email: &str) -> Result<Vec<InboxMessage>> {
+        let messages = MailClient::get_messages(self, email).await?;
+        Ok(messages
+            .into_iter()
+            .map(|msg| InboxMessage {
+                id: msg.mail_id,
+                from: msg.mail_from,
+                subject: msg.mail_subject,
+            })
+            .collect())
+    }
+
+    async fn fetch_body(&self, email: &str, id: &str) -> Result<String> {
+        Ok(MailClient::fetch_email(self, email, id).await?.mail_body)
+    }
+
+    async fn delete_email(&self, email: &str) -> Result<bool> {
+        Ok(MailClient::delete_email(self, email).await?)
+    }
+}
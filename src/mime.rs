@@ -0,0 +1,190 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+/// Maximum depth of nested `multipart/*` entities that is decoded.
+const MAX_DEPTH: usize = 8;
+
+/// Decode an email body into the text of each of its textual parts.
+///
+/// Mail providers hand back bodies in different shapes: already-decoded HTML, quoted-printable text with
+/// its soft line breaks left in, or a full MIME entity with headers and `multipart/*` sections. This
+/// accepts all of them:
+/// - If `raw` starts with a header block declaring `Content-Type` or `Content-Transfer-Encoding`, it is
+///   parsed as a MIME entity. `multipart/*` bodies are split on their boundary, each `text/*` leaf is
+///   decoded according to its transfer encoding, and non-text leaves are skipped.
+/// - Otherwise `raw` is a bare body. It is decoded as quoted-printable if it shows the tell-tale signs
+///   (`=3D` or a soft line break), and returned unchanged if not.
+///
+/// Character sets are not converted; decoded bytes are read as UTF-8, with invalid sequences replaced.
+pub(crate) fn text_parts(raw: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    decode_entity(raw, 0, &mut parts);
+    parts
+}
+
+fn decode_entity(raw: &str, depth: usize, parts: &mut Vec<String>) {
+    let Some((headers, body)) = split_headers(raw) else {
+        parts.push(if looks_quoted_printable(raw) {
+            String::from_utf8_lossy(&decode_quoted_printable(raw)).into_owned()
+        } else {
+            raw.to_string()
+        });
+        return;
+    };
+
+    let content_type = header(&headers, "content-type").unwrap_or("text/plain");
+    let (media_type, params) = content_type.split_once(';').unwrap_or((content_type, ""));
+    let media_type = media_type.trim().to_ascii_lowercase();
+
+    if media_type.starts_with("multipart/") {
+        if depth < MAX_DEPTH
+            && let Some(boundary) = param(params, "boundary")
+        {
+            for part in multipart_sections(body, &boundary) {
+                decode_entity(part, depth + 1, parts);
+            }
+        }
+        return;
+    }
+    if !media_type.starts_with("text/") {
+        return;
+    }
+
+    let encoding = header(&headers, "content-transfer-encoding")
+        .unwrap_or("7bit")
+        .trim()
+        .to_ascii_lowercase();
+    let decoded = match encoding.as_str() {
+        "quoted-printable" => decode_quoted_printable(body),
+        "base64" => {
+            let compact: String = body.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+            // An undecodable part cannot contain a link; skip it rather than fail the whole message
+            let Ok(bytes) = STANDARD.decode(compact) else {
+                return;
+            };
+            bytes
+        }
+        _ => body.as_bytes().to_vec(),
+    };
+    parts.push(String::from_utf8_lossy(&decoded).into_owned());
+}
+
+/// Split a MIME entity into its unfolded headers and its body.
+///
+/// Returns `None` unless every line before the first blank line is a header (or a folded continuation),
+/// and at least one of them describes the content.
+fn split_headers(raw: &str) -> Option<(Vec<(String, String)>, &str)> {
+    let (head, body) = match (raw.find("\r\n\r\n"), raw.find("\n\n")) {
+        (Some(crlf), Some(lf)) if lf < crlf => (&raw[..lf], &raw[lf + 2..]),
+        (Some(crlf), _) => (&raw[..crlf], &raw[crlf + 4..]),
+        (None, Some(lf)) => (&raw[..lf], &raw[lf + 2..]),
+        (None, None) => (raw, ""),
+    };
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in head.lines() {
+        if line.starts_with([' ', '\t']) {
+            let (_, value) = headers.last_mut()?;
+            value.push(' ');
+            value.push_str(line.trim());
+            continue;
+        }
+        let (name, value) = line.split_once(':')?;
+        if name.is_empty() || !name.bytes().all(|b| b.is_ascii_graphic()) {
+            return None;
+        }
+        headers.push((name.to_ascii_lowercase(), value.trim().to_string()));
+    }
+
+    let describes_content = headers
+        .iter()
+        .any(|(name, _)| name == "content-type" || name == "content-transfer-encoding");
+    describes_content.then_some((headers, body))
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Value of the `name` parameter in the `; key=value` list following a media type.
+fn param(params: &str, name: &str) -> Option<String> {
+    params.split(';').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// The sections of a `multipart/*` body between its `--boundary` delimiter lines.
+fn multipart_sections<'a>(body: &'a str, boundary: &str) -> Vec<&'a str> {
+    let delimiter = format!("--{}", boundary);
+    let mut sections = Vec::new();
+    let mut start = None;
+    let mut offset = 0;
+
+    for line in body.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if let Some(rest) = trimmed.strip_prefix(delimiter.as_str())
+            && (rest.is_empty() || rest == "--")
+        {
+            if let Some(start) = start {
+                sections.push(&body[start..offset]);
+            }
+            if rest == "--" {
+                return sections;
+            }
+            start = Some(offset + line.len());
+        }
+        offset += line.len();
+    }
+
+    // Tolerate a missing closing delimiter
+    if let Some(start) = start {
+        sections.push(&body[start..]);
+    }
+    sections
+}
+
+fn looks_quoted_printable(body: &str) -> bool {
+    body.contains("=3D") || body.contains("=\n") || body.contains("=\r\n")
+}
+
+/// Decode quoted-printable text, dropping soft line breaks and leaving malformed escapes as they are.
+fn decode_quoted_printable(body: &str) -> Vec<u8> {
+    let bytes = body.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'=' {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        match bytes.get(i + 1..) {
+            Some([b'\r', b'\n', ..]) => i += 3,
+            Some([b'\n', ..]) => i += 2,
+            Some([hi, lo, ..]) if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() => {
+                out.push(hex_value(*hi) << 4 | hex_value(*lo));
+                i += 3;
+            }
+            _ => {
+                out.push(b'=');
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
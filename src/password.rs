@@ -0,0 +1,286 @@
+use crate::errors::Result;
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use secrecy::SecretString;
+use std::collections::HashSet;
+use std::path::Path;
+use thiserror::Error;
+
+/// Local password strength requirements, checked before any network request.
+///
+/// Configure it with [`crate::AccountGeneratorBuilder::password_policy`]. The default policy requires at
+/// least [`PasswordPolicy::DEFAULT_MIN_LENGTH`] characters and an estimated
+/// [`PasswordPolicy::DEFAULT_MIN_ENTROPY_BITS`] bits of entropy, and has no breached-password list.
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    min_length: usize,
+    min_entropy_bits: f64,
+    breached: HashSet<String>,
+}
+
+/// Why a password was rejected by a [`PasswordPolicy`].
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum PasswordWeakness {
+    /// The password has fewer characters than the policy requires.
+    #[error("password has {length} characters, at least {min_length} are required")]
+    TooShort {
+        /// Number of characters in the password.
+        length: usize,
+        /// Minimum required by the policy.
+        min_length: usize,
+    },
+
+    /// The estimated entropy is below the policy's minimum.
+    #[error(
+        "password has an estimated {bits:.0} bits of entropy, at least {min_bits:.0} are required"
+    )]
+    LowEntropy {
+        /// Estimate returned by [`estimate_entropy`], or [`PasswordGenerator::entropy_bits`] when a
+        /// generator was checked.
+        bits: f64,
+        /// Minimum required by the policy.
+        min_bits: f64,
+    },
+
+    /// The password appears in the policy's breached-password list.
+    #[error("password appears in a list of breached passwords")]
+    Breached,
+}
+
+impl PasswordPolicy {
+    /// Minimum length of the default policy. MEGA's own clients require the same.
+    pub const DEFAULT_MIN_LENGTH: usize = 8;
+    /// Minimum estimated entropy of the default policy, in bits.
+    pub const DEFAULT_MIN_ENTROPY_BITS: f64 = 40.0;
+
+    /// Create the default policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A policy that accepts every password, including the empty one.
+    pub fn permissive() -> Self {
+        Self {
+            min_length: 0,
+            min_entropy_bits: 0.0,
+            breached: HashSet::new(),
+        }
+    }
+
+    /// Require at least `min_length` characters.
+    pub fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Require an [`estimate_entropy`] result of at least `bits`.
+    pub fn min_entropy_bits(mut self, bits: f64) -> Self {
+        self.min_entropy_bits = bits;
+        self
+    }
+
+    /// Reject any of `passwords`, compared exactly. Adds to any list already configured.
+    pub fn breached_passwords<I, S>(mut self, passwords: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.breached.extend(passwords.into_iter().map(Into::into));
+        self
+    }
+
+    /// Reject the passwords listed in the file at `path`, one per line.
+    ///
+    /// Trailing whitespace and empty lines are ignored, so common plain-text lists can be used as they are.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::Io`] if the file cannot be read.
+    pub fn breached_passwords_file(self, path: impl AsRef<Path>) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let list = String::from_utf8_lossy(&bytes);
+        Ok(self.breached_passwords(
+            list.lines()
+                .map(str::trim_end)
+                .filter(|line| !line.is_empty()),
+        ))
+    }
+
+    /// Check `password` against the policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first requirement the password fails, checking length, then the breached list, then
+    /// entropy.
+    pub fn check(&self, password: &str) -> std::result::Result<(), PasswordWeakness> {
+        let length = password.chars().count();
+        if length < self.min_length {
+            return Err(PasswordWeakness::TooShort {
+                length,
+                min_length: self.min_length,
+            });
+        }
+        if self.breached.contains(password) {
+            return Err(PasswordWeakness::Breached);
+        }
+        let bits = estimate_entropy(password);
+        if bits < self.min_entropy_bits {
+            return Err(PasswordWeakness::LowEntropy {
+                bits,
+                min_bits: self.min_entropy_bits,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the passwords `generator` produces against the policy's length and entropy requirements.
+    ///
+    /// Unlike checking one generated password with [`PasswordPolicy::check`], this judges the generator's
+    /// configuration: its length and [`PasswordGenerator::entropy_bits`], which is exact rather than estimated.
+    /// The breached-password list does not apply to random passwords.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PasswordWeakness::TooShort`] or [`PasswordWeakness::LowEntropy`], checking length first.
+    pub fn check_generator(
+        &self,
+        generator: &PasswordGenerator,
+    ) -> std::result::Result<(), PasswordWeakness> {
+        if generator.length < self.min_length {
+            return Err(PasswordWeakness::TooShort {
+                length: generator.length,
+                min_length: self.min_length,
+            });
+        }
+        let bits = generator.entropy_bits();
+        if bits < self.min_entropy_bits {
+            return Err(PasswordWeakness::LowEntropy {
+                bits,
+                min_bits: self.min_entropy_bits,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: Self::DEFAULT_MIN_LENGTH,
+            min_entropy_bits: Self::DEFAULT_MIN_ENTROPY_BITS,
+            breached: HashSet::new(),
+        }
+    }
+}
+
+/// Generator of random account passwords, drawn from the operating system's CSPRNG.
+///
+/// Each call to [`PasswordGenerator::generate`] returns a new password, so every account in a batch can get
+/// its own. Pass it to [`crate::AccountGenerator::generate`] like any other password; it is returned in
+/// [`crate::GeneratedAccount::password`].
+///
+/// ```
+/// use meganz_account_generator::{ExposeSecret, PasswordGenerator};
+///
+/// let password = PasswordGenerator::new().length(24).generate();
+/// assert_eq!(password.expose_secret().chars().count(), 24);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PasswordGenerator {
+    length: usize,
+    alphabet: Vec<char>,
+}
+
+impl PasswordGenerator {
+    /// Length of generated passwords unless configured otherwise.
+    pub const DEFAULT_LENGTH: usize = 20;
+    /// Characters generated passwords are drawn from unless configured otherwise: ASCII letters, digits
+    /// and symbols that need no quoting in a shell or CSV file. `!` is left out because it triggers history
+    /// expansion in interactive bash.
+    pub const DEFAULT_ALPHABET: &'static str =
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_.+=@%";
+
+    /// Create a generator with the default length and alphabet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Generate passwords of `length` characters.
+    pub fn length(mut self, length: usize) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Draw characters uniformly from `alphabet`. Duplicate characters are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alphabet` is empty.
+    pub fn alphabet(mut self, alphabet: &str) -> Self {
+        assert!(!alphabet.is_empty(), "password alphabet must not be empty");
+        let mut seen = HashSet::new();
+        self.alphabet = alphabet.chars().filter(|c| seen.insert(*c)).collect();
+        self
+    }
+
+    /// Generate a new password.
+    pub fn generate(&self) -> SecretString {
+        let password: String = (0..self.length)
+            .map(|_| {
+                *self
+                    .alphabet
+                    .choose(&mut OsRng)
+                    .expect("alphabet is not empty")
+            })
+            .collect();
+        SecretString::from(password)
+    }
+
+    /// Entropy of each generated password in bits: `length * log2(alphabet size)`.
+    pub fn entropy_bits(&self) -> f64 {
+        self.length as f64 * (self.alphabet.len() as f64).log2()
+    }
+}
+
+impl Default for PasswordGenerator {
+    fn default() -> Self {
+        Self {
+            length: Self::DEFAULT_LENGTH,
+            alphabet: Self::DEFAULT_ALPHABET.chars().collect(),
+        }
+    }
+}
+
+/// Estimate the entropy of `password` in bits.
+///
+/// The estimate is the number of characters times `log2` of the size of the character classes used
+/// (lowercase, uppercase, digits, ASCII symbols and other characters). Repeats of a character already seen
+/// count half, so padding a password with one character adds little. It is a coarse upper bound: it
+/// cannot recognize dictionary words or keyboard patterns, which is what the breached-password list is for.
+pub fn estimate_entropy(password: &str) -> f64 {
+    let mut pool = 0u32;
+    let mut classes = [false; 5];
+    for c in password.chars() {
+        let class = match c {
+            'a'..='z' => 0,
+            'A'..='Z' => 1,
+            '0'..='9' => 2,
+            c if c.is_ascii_punctuation() || c == ' ' => 3,
+            _ => 4,
+        };
+        if !classes[class] {
+            classes[class] = true;
+            pool += [26, 26, 10, 33, 100][class];
+        }
+    }
+    if pool == 0 {
+        return 0.0;
+    }
+
+    let mut seen = HashSet::new();
+    let effective_length: f64 = password
+        .chars()
+        .map(|c| if seen.insert(c) { 1.0 } else { 0.5 })
+        .sum();
+    effective_length * f64::from(pool).log2()
+}
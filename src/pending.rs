@@ -0,0 +1,171 @@
+use crate::errors::{Error, Result};
+use megalib::RegistrationState;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const HEADER: &str = "meganz-pending-registration v1";
+
+/// A MEGA registration that has been submitted but not yet confirmed.
+///
+/// Returned by [`crate::AccountGenerator::start_registration`] and consumed by
+/// [`crate::AccountGenerator::resume`]. Persist it with [`PendingRegistration::save`] (or
+/// [`PendingRegistration::serialize`]) between the two calls so that a long wait for the confirmation email
+/// survives process restarts.
+///
+/// # Security
+///
+/// The registration state contains a key derived from the account password. The `Debug` implementation
+/// redacts it, and [`PendingRegistration::save`] creates files readable only by the owner on Unix.
+#[derive(Clone)]
+pub struct PendingRegistration {
+    /// Temporary email address the registration was submitted for.
+    pub email: String,
+    /// Account display name used during signup.
+    pub name: String,
+    /// State returned by MEGA's registration step, required for verification.
+    pub state: RegistrationState,
+    /// Time after which polling for the confirmation email gives up.
+    pub deadline: SystemTime,
+}
+
+impl std::fmt::Debug for PendingRegistration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingRegistration")
+            .field("email", &self.email)
+            .field("name", &self.name)
+            .field("state", &"[REDACTED]")
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
+
+impl PendingRegistration {
+    /// Serialize to a line-based text format.
+    ///
+    /// Format (backslashes and line breaks in `email` and `name` are escaped as `\\`, `\n` and `\r`):
+    ///
+    /// ```text
+    /// meganz-pending-registration v1
+    /// email: <address>
+    /// name: <display name>
+    /// state: <megalib RegistrationState::serialize()>
+    /// deadline: <unix seconds>
+    /// ```
+    pub fn serialize(&self) -> String {
+        let deadline = self
+            .deadline
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        format!(
+            "{}\nemail: {}\nname: {}\nstate: {}\ndeadline: {}\n",
+            HEADER,
+            escape(&self.email),
+            escape(&self.name),
+            self.state.serialize(),
+            deadline
+        )
+    }
+
+    /// Parse the format produced by [`PendingRegistration::serialize`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidPendingRegistration`] if the header, a field, or the registration state is
+    /// missing or malformed.
+    pub fn deserialize(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidPendingRegistration(reason.to_string());
+
+        let mut lines = s.lines();
+        if lines.next().map(str::trim_end) != Some(HEADER) {
+            return Err(invalid("unrecognized header"));
+        }
+
+        let (mut email, mut name, mut state, mut deadline) = (None, None, None, None);
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let (key, value) = line
+                .split_once(": ")
+                .ok_or_else(|| invalid("expected `key: value`"))?;
+            match key {
+                "email" => email = Some(unescape(value)),
+                "name" => name = Some(unescape(value)),
+                "state" => {
+                    let parsed = RegistrationState::deserialize(value)
+                        .map_err(|_| invalid("malformed registration state"))?;
+                    state = Some(parsed);
+                }
+                "deadline" => {
+                    let secs = value
+                        .parse::<u64>()
+                        .map_err(|_| invalid("malformed deadline"))?;
+                    deadline = Some(UNIX_EPOCH + Duration::from_secs(secs));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            email: email.ok_or_else(|| invalid("missing email"))?,
+            name: name.ok_or_else(|| invalid("missing name"))?,
+            state: state.ok_or_else(|| invalid("missing state"))?,
+            deadline: deadline.ok_or_else(|| invalid("missing deadline"))?,
+        })
+    }
+
+    /// Write the serialized registration to `path`, replacing any existing file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path)?;
+        file.write_all(self.serialize().as_bytes())?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Read a registration previously written by [`PendingRegistration::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::deserialize(&std::fs::read_to_string(path)?)
+    }
+
+    /// Whether the deadline for receiving the confirmation email has passed.
+    pub fn is_expired(&self) -> bool {
+        SystemTime::now() >= self.deadline
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some('r')) => {
+                out.push('\r');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                out.push('\\');
+                chars.next();
+            }
+            (c, _) => out.push(c),
+        }
+    }
+    out
+}
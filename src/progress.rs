@@ -0,0 +1,118 @@
+use crate::errors::Stage;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Progress reported while [`crate::AccountGenerator`] runs.
+///
+/// Events are delivered in order to the handler registered with
+/// [`crate::AccountGeneratorBuilder::on_progress`]. Secrets (passwords and confirmation keys) are never
+/// included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProgressEvent {
+    /// A temporary inbox was created.
+    InboxCreated {
+        /// Address of the new inbox.
+        email: String,
+    },
+    /// MEGA accepted the registration and should now send a confirmation email.
+    RegistrationSubmitted {
+        /// Address being registered.
+        email: String,
+    },
+    /// The inbox was checked for new messages.
+    Polled {
+        /// 1-based poll attempt number.
+        attempt: u32,
+        /// Number of messages currently in the inbox.
+        messages: usize,
+    },
+    /// A likely MEGA email was seen for the first time.
+    MegaEmailSeen {
+        /// Sender reported by the mail provider.
+        from: String,
+        /// Subject line.
+        subject: String,
+    },
+    /// A request failed with a retryable error and will be repeated after `delay`.
+    RetryScheduled {
+        /// Stage the request belongs to.
+        stage: Stage,
+        /// 1-based number of the retry that is scheduled.
+        attempt: u32,
+        /// Time to wait before retrying.
+        delay: Duration,
+    },
+    /// A confirmation key was extracted from a MEGA email or a link supplied by the caller.
+    ConfirmKeyExtracted,
+    /// MEGA confirmed the registration.
+    Verified {
+        /// Address of the confirmed account.
+        email: String,
+    },
+    /// The post-confirmation login succeeded.
+    LoggedIn {
+        /// Address of the account.
+        email: String,
+        /// Total storage quota in bytes.
+        storage_total: u64,
+        /// Used storage in bytes.
+        storage_used: u64,
+    },
+    /// The recovery key of a confirmed account was exported. The key itself is not included.
+    RecoveryKeyExported {
+        /// Address of the account.
+        email: String,
+    },
+    /// MEGA accepted a request to move an account to a new address and should now send it a link.
+    EmailChangeRequested {
+        /// Current address of the account.
+        email: String,
+        /// Address the account is moving to.
+        new_email: String,
+    },
+    /// MEGA confirmed an email change.
+    EmailChanged {
+        /// Address the account used before.
+        old_email: String,
+        /// New address of the account.
+        email: String,
+    },
+    /// MEGA accepted a request to cancel an account and should now send a cancellation link.
+    CancellationRequested {
+        /// Address of the account.
+        email: String,
+    },
+    /// MEGA deleted an account.
+    AccountCancelled {
+        /// Address of the deleted account.
+        email: String,
+    },
+    /// Deletion of the temporary inbox was attempted.
+    InboxDeleted {
+        /// Address of the inbox.
+        email: String,
+        /// Whether the mail provider reported the inbox as deleted.
+        deleted: bool,
+    },
+}
+
+/// Shared callback invoked for each [`ProgressEvent`].
+#[derive(Clone)]
+pub(crate) struct ProgressHandler(Arc<dyn Fn(&ProgressEvent) + Send + Sync>);
+
+impl ProgressHandler {
+    pub(crate) fn new(handler: impl Fn(&ProgressEvent) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(handler))
+    }
+
+    pub(crate) fn emit(&self, event: ProgressEvent) {
+        (self.0)(&event);
+    }
+}
+
+impl std::fmt::Debug for ProgressHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressHandler")
+    }
+}
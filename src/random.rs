@@ -1,8 +1,7 @@
 use rand::Rng;
 
-/// Generate a random email alias.
-pub(crate) fn generate_random_alias() -> String {
-    let mut rng = rand::thread_rng();
+/// Generate a random email alias from `rng`.
+pub(crate) fn generate_random_alias(rng: &mut impl Rng) -> String {
     let adjectives = [
         "ashen", "bleak", "civic", "cold", "covert", "drift", "echo", "grim", "iron", "kilo",
         "latent", "mute", "neon", "noir", "null", "omni", "pale", "quiet", "shadow", "silent",
@@ -73,9 +72,8 @@ pub(crate) fn generate_random_alias() -> String {
     )
 }
 
-/// Generate a random name.
-pub(crate) fn generate_random_name() -> String {
-    let mut rng = rand::thread_rng();
+/// Generate a random name from `rng`.
+pub(crate) fn generate_random_name(rng: &mut impl Rng) -> String {
     let first_names = [
         "Amina",
         "Chidi",
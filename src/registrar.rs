@@ -0,0 +1,271 @@
+use crate::account::AccountSession;
+use crate::errors::Result;
+use crate::proxy::ProxyConfig;
+use megalib::api::ApiClient;
+use megalib::base64::{base64url_decode, base64url_encode};
+use megalib::crypto::{decrypt_key, derive_key_v2, make_password_key, make_username_hash};
+use megalib::{MegaError, RegistrationState, Session, register, verify_registration};
+use secrecy::SecretString;
+use serde_json::json;
+use std::future::Future;
+
+/// Account registration backend used by [`crate::AccountGenerator`].
+///
+/// The default implementation is [`MegaRegistrar`], which talks to MEGA through `megalib`. Tests can use
+/// `FakeRegistrar`, behind the `test-util` feature, to run the generator without network access.
+pub trait Registrar {
+    /// Start a registration for `email` and return the state needed to confirm it later.
+    fn register(
+        &self,
+        email: &str,
+        password: &str,
+        name: &str,
+    ) -> impl Future<Output = Result<RegistrationState>> + Send;
+
+    /// Confirm a registration using the key extracted from the confirmation email.
+    fn verify(
+        &self,
+        state: &RegistrationState,
+        confirm_key: &str,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Log in to a confirmed account and report its session and storage quota.
+    ///
+    /// Used by [`crate::AccountGeneratorBuilder::login_check`]. The default implementation reports that login
+    /// is not supported.
+    fn login(
+        &self,
+        email: &str,
+        password: &str,
+    ) -> impl Future<Output = Result<AccountSession>> + Send {
+        let _ = (email, password);
+        async {
+            Err(MegaError::Custom("login is not supported by this registrar".to_string()).into())
+        }
+    }
+
+    /// Export the recovery key of a confirmed account: its master key, base64url-encoded as MEGA's clients
+    /// write it.
+    ///
+    /// Used by [`crate::AccountGeneratorBuilder::export_recovery_key`]. The default implementation reports
+    /// that recovery key export is not supported.
+    fn recovery_key(
+        &self,
+        email: &str,
+        password: &str,
+    ) -> impl Future<Output = Result<SecretString>> + Send {
+        let _ = (email, password);
+        async {
+            Err(MegaError::Custom(
+                "recovery key export is not supported by this registrar".to_string(),
+            )
+            .into())
+        }
+    }
+
+    /// Ask MEGA to cancel the account for `email`.
+    ///
+    /// MEGA then sends a `#cancel<KEY>` link to `email`; the account is deleted once that key is passed to
+    /// [`Registrar::confirm_cancellation`]. The default implementation reports that cancellation is not
+    /// supported.
+    fn request_cancellation(
+        &self,
+        email: &str,
+        password: &str,
+    ) -> impl Future<Output = Result<()>> + Send {
+        let _ = (email, password);
+        async {
+            Err(MegaError::Custom(
+                "account cancellation is not supported by this registrar".to_string(),
+            )
+            .into())
+        }
+    }
+
+    /// Delete the account for `email` using the key from the cancellation link.
+    ///
+    /// The default implementation reports that cancellation is not supported.
+    fn confirm_cancellation(
+        &self,
+        email: &str,
+        password: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<()>> + Send {
+        let _ = (email, password, key);
+        async {
+            Err(MegaError::Custom(
+                "account cancellation is not supported by this registrar".to_string(),
+            )
+            .into())
+        }
+    }
+
+    /// Ask MEGA to move a confirmed account from `email` to `new_email`.
+    ///
+    /// MEGA then sends a `#verify<KEY>` link to `new_email`; the change takes effect once that key is passed
+    /// to [`Registrar::confirm_email_change`]. The default implementation reports that email changes are not
+    /// supported.
+    fn request_email_change(
+        &self,
+        email: &str,
+        password: &str,
+        new_email: &str,
+    ) -> impl Future<Output = Result<()>> + Send {
+        let _ = (email, password, new_email);
+        async {
+            Err(
+                MegaError::Custom("email changes are not supported by this registrar".to_string())
+                    .into(),
+            )
+        }
+    }
+
+    /// Complete an email change using the key from the link MEGA sent to `new_email`.
+    ///
+    /// The default implementation reports that email changes are not supported.
+    fn confirm_email_change(
+        &self,
+        email: &str,
+        password: &str,
+        new_email: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<()>> + Send {
+        let _ = (email, password, new_email, key);
+        async {
+            Err(
+                MegaError::Custom("email changes are not supported by this registrar".to_string())
+                    .into(),
+            )
+        }
+    }
+}
+
+/// [`Registrar`] backed by `megalib::register` and `megalib::verify_registration`.
+#[derive(Debug, Clone, Default)]
+pub struct MegaRegistrar {
+    proxy: Option<ProxyConfig>,
+}
+
+impl MegaRegistrar {
+    /// Create a registrar that optionally routes MEGA requests through `proxy`.
+    pub fn new(proxy: Option<ProxyConfig>) -> Self {
+        Self { proxy }
+    }
+
+    fn proxy_url(&self) -> Option<String> {
+        self.proxy.as_ref().map(ProxyConfig::url)
+    }
+
+    async fn session(&self, email: &str, password: &str) -> Result<Session> {
+        Ok(match self.proxy_url().as_deref() {
+            Some(proxy) => Session::login_with_proxy(email, password, proxy).await?,
+            None => Session::login(email, password).await?,
+        })
+    }
+
+    /// Log in and return an API client authenticated with the new session.
+    async fn authenticated_api(&self, email: &str, password: &str) -> Result<ApiClient> {
+        let session = self.session(email, password).await?;
+        let mut api = match self.proxy_url().as_deref() {
+            Some(proxy) => ApiClient::with_proxy(proxy)?,
+            None => ApiClient::new(),
+        };
+        api.set_session_id(session.session_id().to_string());
+        Ok(api)
+    }
+}
+
+/// Look up the account version of `email` and derive its password key, returning both.
+async fn password_key(api: &mut ApiClient, email: &str, password: &str) -> Result<(i64, [u8; 16])> {
+    let pre_login = api
+        .request(json!({"a": "us0", "user": email.to_lowercase()}))
+        .await?;
+    let version = pre_login["v"].as_i64().unwrap_or(1);
+    if version == 2 {
+        let salt = pre_login["s"].as_str().ok_or(MegaError::InvalidResponse)?;
+        let derived = derive_key_v2(password, &base64url_decode(salt).map_err(MegaError::from)?)?;
+        let mut key = [0; 16];
+        key.copy_from_slice(&derived[..16]);
+        Ok((version, key))
+    } else {
+        Ok((version, make_password_key(password)))
+    }
+}
+
+impl Registrar for MegaRegistrar {
+    async fn register(&self, email: &str, password: &str, name: &str) -> Result<RegistrationState> {
+        let proxy = self.proxy_url();
+        Ok(register(email, password, name, proxy.as_deref()).await?)
+    }
+
+    async fn verify(&self, state: &RegistrationState, confirm_key: &str) -> Result<()> {
+        let proxy = self.proxy_url();
+        Ok(verify_registration(state, confirm_key, proxy.as_deref()).await?)
+    }
+
+    async fn login(&self, email: &str, password: &str) -> Result<AccountSession> {
+        let mut session = self.session(email, password).await?;
+        let quota = session.quota().await?;
+        Ok(AccountSession {
+            session: SecretString::from(session.dump_session()?),
+            storage_total: quota.total,
+            storage_used: quota.used,
+        })
+    }
+
+    async fn recovery_key(&self, email: &str, password: &str) -> Result<SecretString> {
+        let mut api = self.authenticated_api(email, password).await?;
+
+        // The session only holds the master key in memory; fetch it again encrypted with the password key
+        let (_, password_key) = password_key(&mut api, email, password).await?;
+        let user = api.request(json!({"a": "ug"})).await?;
+        let encrypted = user["k"].as_str().ok_or(MegaError::InvalidResponse)?;
+        let master_key = decrypt_key(encrypted, &password_key)?;
+        Ok(SecretString::from(base64url_encode(&master_key)))
+    }
+
+    async fn request_cancellation(&self, email: &str, password: &str) -> Result<()> {
+        let mut api = self.authenticated_api(email, password).await?;
+        // Recovery link type 21 is account cancellation
+        api.request(json!({"a": "erm", "m": email, "t": 21}))
+            .await?;
+        Ok(())
+    }
+
+    async fn confirm_cancellation(&self, email: &str, password: &str, key: &str) -> Result<()> {
+        let mut api = self.authenticated_api(email, password).await?;
+        api.request(json!({"a": "erx", "c": key})).await?;
+        Ok(())
+    }
+
+    async fn request_email_change(
+        &self,
+        email: &str,
+        password: &str,
+        new_email: &str,
+    ) -> Result<()> {
+        let mut api = self.authenticated_api(email, password).await?;
+        api.request(json!({"a": "se", "aa": "a", "e": new_email}))
+            .await?;
+        Ok(())
+    }
+
+    async fn confirm_email_change(
+        &self,
+        email: &str,
+        password: &str,
+        new_email: &str,
+        key: &str,
+    ) -> Result<()> {
+        let mut api = self.authenticated_api(email, password).await?;
+        let mut request = json!({"a": "sec", "c": key, "e": new_email, "r": 1});
+        // As in the SDK, v1 accounts prove the password with the login hash of the new address
+        let (version, password_key) = password_key(&mut api, email, password).await?;
+        if version != 2 {
+            let login_hash = make_username_hash(&new_email.to_lowercase(), &password_key);
+            request["uh"] = base64url_encode(&login_hash).into();
+        }
+        api.request(request).await?;
+        Ok(())
+    }
+}
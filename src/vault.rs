@@ -0,0 +1,497 @@
+//! Passphrase-encrypted credential vault.
+//!
+//! A vault is a single file that entries are appended to, or that [`Vault::rewrite`] replaces as a whole:
+//!
+//! ```text
+//! magic     8 bytes   b"MEGAVLT\0"
+//! version   1 byte    VAULT_VERSION
+//! m_cost    u32 LE    Argon2id memory cost (KiB)
+//! t_cost    u32 LE    Argon2id iterations
+//! p_cost    u32 LE    Argon2id parallelism
+//! salt      16 bytes
+//! check     24-byte nonce + sealed u64 LE record count (proves the passphrase)
+//! records   repeated: u32 LE ciphertext length, 24-byte nonce, ciphertext
+//! ```
+//!
+//! The key is derived from the passphrase with Argon2id and every record is sealed with
+//! XChaCha20-Poly1305. The header fields before `check` are bound to every ciphertext as associated data,
+//! so records cannot be moved between vaults or decrypted under altered KDF parameters. Each record's
+//! associated data also carries its position, and `check` holds the number of records, so records cannot be
+//! reordered, duplicated or dropped without the vault reading as [`VaultError::Corrupt`]; only the whole file
+//! can be rolled back to an earlier state. KDF parameters above fixed limits are rejected the same way, so a
+//! crafted header cannot make opening a vault arbitrarily slow.
+//!
+//! Version 1 vaults, whose `check` seals nothing and whose records carry no position, can still be opened
+//! and read. The first [`Vault::append`] or [`Vault::rewrite`] upgrades them to the current version.
+
+use crate::account::GeneratedAccount;
+use crate::errors::Result;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use secrecy::zeroize::Zeroizing;
+use secrecy::{ExposeSecret, SecretString};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Current vault format version written by [`Vault::create`].
+pub const VAULT_VERSION: u8 = 2;
+/// Format version without record positions or a record count, still accepted by [`Vault::open`].
+const LEGACY_VERSION: u8 = 1;
+
+const MAGIC: &[u8; 8] = b"MEGAVLT\0";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+/// Magic, version, three KDF parameters and the salt.
+const PARAMS_LEN: usize = MAGIC.len() + 1 + 12 + SALT_LEN;
+/// Length of the record count sealed in `check`.
+const COUNT_LEN: usize = 8;
+/// Upper bound on Argon2 memory cost accepted from a file header (1 GiB).
+const MAX_M_COST: u32 = 1024 * 1024;
+/// Upper bound on Argon2 iterations accepted from a file header.
+const MAX_T_COST: u32 = 16;
+/// Upper bound on Argon2 parallelism accepted from a file header.
+const MAX_P_COST: u32 = 16;
+/// Upper bound on a single record, to reject corrupt length prefixes before allocating.
+const MAX_RECORD_LEN: usize = 1024 * 1024;
+
+const TAG_EMAIL: u8 = 1;
+const TAG_PASSWORD: u8 = 2;
+const TAG_NAME: u8 = 3;
+const TAG_RECOVERY_KEY: u8 = 4;
+
+/// Errors specific to reading and writing a [`Vault`].
+#[derive(Debug, Error)]
+pub enum VaultError {
+    /// The vault file could not be read or written.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// [`Vault::create`] was called for a path that already exists.
+    #[error("Vault already exists")]
+    AlreadyExists,
+
+    /// The file does not start with the vault magic bytes.
+    #[error("Not a credential vault")]
+    NotAVault,
+
+    /// The file was written by a newer, unsupported format version.
+    #[error("Unsupported vault version {0}")]
+    UnsupportedVersion(u8),
+
+    /// The passphrase does not match the one the vault was created with.
+    #[error("Wrong vault passphrase")]
+    WrongPassphrase,
+
+    /// The file is truncated, a record failed authentication or is out of place, or records are missing.
+    #[error("Vault is corrupt")]
+    Corrupt,
+}
+
+/// A credential stored in a [`Vault`].
+#[derive(Debug, Clone)]
+pub struct VaultEntry {
+    /// Account email address.
+    pub email: String,
+    /// Account password.
+    pub password: SecretString,
+    /// Account display name.
+    pub name: String,
+    /// Account recovery key, if it was exported.
+    pub recovery_key: Option<SecretString>,
+}
+
+impl From<&GeneratedAccount> for VaultEntry {
+    fn from(account: &GeneratedAccount) -> Self {
+        Self {
+            email: account.email.clone(),
+            password: account.password.clone(),
+            name: account.name.clone(),
+            recovery_key: account.recovery_key.clone(),
+        }
+    }
+}
+
+/// Handle to an unlocked credential vault file.
+///
+/// The derived key is kept in memory (and zeroized on drop) so entries can be appended and read without
+/// repeating the key derivation.
+pub struct Vault {
+    path: PathBuf,
+    params: [u8; PARAMS_LEN],
+    cipher: XChaCha20Poly1305,
+}
+
+impl std::fmt::Debug for Vault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Vault")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Vault {
+    /// Create a new, empty vault at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VaultError::AlreadyExists`] if `path` exists, or [`VaultError::Io`] if it cannot be written.
+    pub fn create(path: impl AsRef<Path>, passphrase: &SecretString) -> Result<Self> {
+        let path = path.as_ref();
+        let kdf = Params::default();
+
+        let mut params = [0u8; PARAMS_LEN];
+        params[..8].copy_from_slice(MAGIC);
+        params[8] = VAULT_VERSION;
+        params[9..13].copy_from_slice(&kdf.m_cost().to_le_bytes());
+        params[13..17].copy_from_slice(&kdf.t_cost().to_le_bytes());
+        params[17..21].copy_from_slice(&kdf.p_cost().to_le_bytes());
+        OsRng.fill_bytes(&mut params[21..]);
+
+        let vault = Self::unlock(path, params, passphrase)?;
+        let check = vault.seal(&0u64.to_le_bytes(), &params)?;
+
+        let mut file = new_file(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => VaultError::AlreadyExists,
+            _ => VaultError::Io(e),
+        })?;
+        file.write_all(&params).map_err(VaultError::Io)?;
+        file.write_all(&check).map_err(VaultError::Io)?;
+        file.sync_all().map_err(VaultError::Io)?;
+
+        Ok(vault)
+    }
+
+    /// Open an existing vault at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VaultError::WrongPassphrase`] if `passphrase` does not unlock the vault, and
+    /// [`VaultError::NotAVault`], [`VaultError::UnsupportedVersion`] or [`VaultError::Corrupt`] if the file
+    /// header cannot be read or asks for KDF parameters above the supported limits.
+    pub fn open(path: impl AsRef<Path>, passphrase: &SecretString) -> Result<Self> {
+        let path = path.as_ref();
+        let mut file = File::open(path).map_err(VaultError::Io)?;
+        let (params, check) = read_header(&mut file)?;
+
+        let vault = Self::unlock(path, params, passphrase)?;
+        vault
+            .open_sealed(&check, &params)
+            .map_err(|_| VaultError::WrongPassphrase)?;
+
+        Ok(vault)
+    }
+
+    /// Open the vault at `path`, creating it first if it does not exist.
+    pub fn open_or_create(path: impl AsRef<Path>, passphrase: &SecretString) -> Result<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            Self::open(path, passphrase)
+        } else {
+            Self::create(path, passphrase)
+        }
+    }
+
+    /// Path of the vault file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Encrypt `entry` and append it to the vault.
+    ///
+    /// A version 1 vault is rewritten in the current format first, see [`Vault::rewrite`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VaultError::Corrupt`] if the existing entries fail to read, as with [`Vault::entries`].
+    pub fn append(&mut self, entry: &VaultEntry) -> Result<()> {
+        let mut entries = self.entries()?;
+        if self.params[8] == LEGACY_VERSION {
+            entries.push(entry.clone());
+            return self.rewrite(&entries);
+        }
+
+        let index = entries.len() as u64;
+        let record = self.record(&self.params, index, entry)?;
+        let check = self.seal(&(index + 1).to_le_bytes(), &self.params)?;
+
+        // The record goes in before the count that covers it, so an interrupted append leaves at most a
+        // record beyond the count, which is still read
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(&self.path)
+            .map_err(VaultError::Io)?;
+        file.seek(SeekFrom::End(0)).map_err(VaultError::Io)?;
+        file.write_all(&record).map_err(VaultError::Io)?;
+        file.sync_all().map_err(VaultError::Io)?;
+        file.seek(SeekFrom::Start(PARAMS_LEN as u64))
+            .map_err(VaultError::Io)?;
+        file.write_all(&check).map_err(VaultError::Io)?;
+        file.sync_all().map_err(VaultError::Io)?;
+        Ok(())
+    }
+
+    /// Replace every entry in the vault with `entries`, in order.
+    ///
+    /// The new contents are written to a temporary file next to the vault, which is then renamed over it, so
+    /// an interrupted rewrite leaves either the old or the new entries. The passphrase and KDF parameters are
+    /// kept, and the file is written in the current format version.
+    pub fn rewrite(&mut self, entries: &[VaultEntry]) -> Result<()> {
+        let mut params = self.params;
+        params[8] = VAULT_VERSION;
+        let mut contents = params.to_vec();
+        contents.extend_from_slice(&self.seal(&(entries.len() as u64).to_le_bytes(), &params)?);
+        for (index, entry) in entries.iter().enumerate() {
+            contents.extend_from_slice(&self.record(&params, index as u64, entry)?);
+        }
+
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        let written = new_file(&tmp_path).and_then(|mut file| {
+            file.write_all(&contents)?;
+            file.sync_all()
+        });
+        if let Err(e) = written.and_then(|()| std::fs::rename(&tmp_path, &self.path)) {
+            let _ = std::fs::remove_file(&tmp_path);
+            return Err(VaultError::Io(e).into());
+        }
+        self.params = params;
+        Ok(())
+    }
+
+    /// Decrypt and return every entry in the vault, in the order they were appended.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VaultError::Corrupt`] if the file header changed since the vault was opened, a record is
+    /// truncated, fails authentication or is out of place, or records are missing.
+    pub fn entries(&self) -> Result<Vec<VaultEntry>> {
+        let mut data = Vec::new();
+        File::open(&self.path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .map_err(VaultError::Io)?;
+
+        let header_len = PARAMS_LEN + check_len(self.params[8]);
+        if data.len() < header_len || data[..PARAMS_LEN] != self.params {
+            return Err(VaultError::Corrupt.into());
+        }
+        let count = match self.params[8] {
+            LEGACY_VERSION => 0,
+            _ => {
+                let count = self.open_sealed(&data[PARAMS_LEN..header_len], &self.params)?;
+                u64::from_le_bytes(
+                    count
+                        .as_slice()
+                        .try_into()
+                        .map_err(|_| VaultError::Corrupt)?,
+                )
+            }
+        };
+
+        let mut entries = Vec::new();
+        let mut rest = &data[header_len..];
+        while !rest.is_empty() {
+            let (len, tail) = rest.split_first_chunk::<4>().ok_or(VaultError::Corrupt)?;
+            let len = NONCE_LEN + u32::from_le_bytes(*len) as usize;
+            if len > MAX_RECORD_LEN || tail.len() < len {
+                return Err(VaultError::Corrupt.into());
+            }
+            let (record, tail) = tail.split_at(len);
+            let plaintext =
+                self.open_sealed(record, &record_aad(&self.params, entries.len() as u64))?;
+            entries.push(decode_entry(&plaintext)?);
+            rest = tail;
+        }
+        if (entries.len() as u64) < count {
+            return Err(VaultError::Corrupt.into());
+        }
+
+        Ok(entries)
+    }
+
+    /// Encrypt `entry` as the length-prefixed record at `index` of a vault with header `params`.
+    fn record(&self, params: &[u8; PARAMS_LEN], index: u64, entry: &VaultEntry) -> Result<Vec<u8>> {
+        let plaintext = encode_entry(entry);
+        let sealed = self.seal(&plaintext, &record_aad(params, index))?;
+        let ciphertext_len = (sealed.len() - NONCE_LEN) as u32;
+
+        let mut record = Vec::with_capacity(4 + sealed.len());
+        record.extend_from_slice(&ciphertext_len.to_le_bytes());
+        record.extend_from_slice(&sealed);
+        Ok(record)
+    }
+
+    fn unlock(path: &Path, params: [u8; PARAMS_LEN], passphrase: &SecretString) -> Result<Self> {
+        let field = |at: usize| u32::from_le_bytes(params[at..at + 4].try_into().expect("u32"));
+        let (m_cost, t_cost, p_cost) = (field(9), field(13), field(17));
+        if m_cost > MAX_M_COST || t_cost > MAX_T_COST || p_cost > MAX_P_COST {
+            return Err(VaultError::Corrupt.into());
+        }
+        let kdf =
+            Params::new(m_cost, t_cost, p_cost, Some(KEY_LEN)).map_err(|_| VaultError::Corrupt)?;
+
+        let mut key = Zeroizing::new([0u8; KEY_LEN]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, kdf)
+            .hash_password_into(
+                passphrase.expose_secret().as_bytes(),
+                &params[21..],
+                key.as_mut(),
+            )
+            .map_err(|_| VaultError::Corrupt)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            params,
+            cipher: XChaCha20Poly1305::new(key.as_ref().into()),
+        })
+    }
+
+    /// Encrypt `plaintext` bound to `aad`, returning the nonce followed by the ciphertext.
+    fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|_| VaultError::Corrupt)?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypt a nonce-prefixed ciphertext produced by [`Vault::seal`] with the same `aad`.
+    fn open_sealed(&self, sealed: &[u8], aad: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+        if sealed.len() < NONCE_LEN + TAG_LEN {
+            return Err(VaultError::Corrupt.into());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| VaultError::Corrupt.into())
+    }
+}
+
+/// Read the header fields before `check`, and `check` itself.
+fn read_header(file: &mut File) -> Result<([u8; PARAMS_LEN], Vec<u8>)> {
+    let mut params = [0u8; PARAMS_LEN];
+    file.read_exact(&mut params[..MAGIC.len()])
+        .map_err(|_| VaultError::NotAVault)?;
+    if &params[..MAGIC.len()] != MAGIC {
+        return Err(VaultError::NotAVault.into());
+    }
+    file.read_exact(&mut params[MAGIC.len()..])
+        .map_err(|_| VaultError::Corrupt)?;
+
+    match params[8] {
+        LEGACY_VERSION | VAULT_VERSION => {}
+        version => return Err(VaultError::UnsupportedVersion(version).into()),
+    }
+    let mut check = vec![0u8; check_len(params[8])];
+    file.read_exact(&mut check)
+        .map_err(|_| VaultError::Corrupt)?;
+    Ok((params, check))
+}
+
+/// Length of `check` in a vault of format `version`.
+fn check_len(version: u8) -> usize {
+    match version {
+        LEGACY_VERSION => NONCE_LEN + TAG_LEN,
+        _ => NONCE_LEN + COUNT_LEN + TAG_LEN,
+    }
+}
+
+/// Associated data of the record at `index` in a vault with header `params`.
+fn record_aad(params: &[u8; PARAMS_LEN], index: u64) -> Vec<u8> {
+    let mut aad = params.to_vec();
+    if params[8] != LEGACY_VERSION {
+        aad.extend_from_slice(&index.to_le_bytes());
+    }
+    aad
+}
+
+fn new_file(path: &Path) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+/// Encode an entry as a sequence of `tag (u8), length (u32 LE), bytes` fields.
+fn encode_entry(entry: &VaultEntry) -> Zeroizing<Vec<u8>> {
+    let mut out = Zeroizing::new(Vec::new());
+    let recovery_key = entry
+        .recovery_key
+        .as_ref()
+        .map(|key| (TAG_RECOVERY_KEY, key.expose_secret()));
+    for (tag, value) in [
+        (TAG_EMAIL, entry.email.as_str()),
+        (TAG_PASSWORD, entry.password.expose_secret()),
+        (TAG_NAME, entry.name.as_str()),
+    ]
+    .into_iter()
+    .chain(recovery_key)
+    {
+        out.push(tag);
+        out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        out.extend_from_slice(value.as_bytes());
+    }
+    out
+}
+
+/// Decode an entry written by [`encode_entry`]. Unknown tags are skipped so newer writers can add fields.
+fn decode_entry(mut data: &[u8]) -> Result<VaultEntry> {
+    let mut email = None;
+    let mut password = None;
+    let mut name = None;
+    let mut recovery_key = None;
+
+    while let Some((&tag, rest)) = data.split_first() {
+        let (len, rest) = rest.split_first_chunk::<4>().ok_or(VaultError::Corrupt)?;
+        let len = u32::from_le_bytes(*len) as usize;
+        if rest.len() < len {
+            return Err(VaultError::Corrupt.into());
+        }
+        let (value, rest) = rest.split_at(len);
+        let value = std::str::from_utf8(value).map_err(|_| VaultError::Corrupt)?;
+        match tag {
+            TAG_EMAIL => email = Some(value.to_string()),
+            TAG_PASSWORD => password = Some(SecretString::from(value)),
+            TAG_NAME => name = Some(value.to_string()),
+            TAG_RECOVERY_KEY => recovery_key = Some(SecretString::from(value)),
+            _ => {}
+        }
+        data = rest;
+    }
+
+    Ok(VaultEntry {
+        email: email.ok_or(VaultError::Corrupt)?,
+        password: password.ok_or(VaultError::Corrupt)?,
+        name: name.unwrap_or_default(),
+        recovery_key,
+    })
+}
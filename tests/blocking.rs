@@ -0,0 +1,88 @@
+use meganz_account_generator::blocking::BlockingAccountGenerator;
+use meganz_account_generator::{
+    AccountGenerator, CancellationToken, Error, FakeMailProvider, FakeRegistrar,
+};
+use std::time::Duration;
+
+fn fake_generator() -> (
+    BlockingAccountGenerator<FakeMailProvider, FakeRegistrar>,
+    FakeMailProvider,
+    FakeRegistrar,
+) {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let generator = AccountGenerator::builder()
+        .timeout(Duration::from_millis(200))
+        .poll_interval(Duration::from_millis(10))
+        .build_with(mail.clone(), registrar.clone());
+    (
+        BlockingAccountGenerator::from_async(generator).unwrap(),
+        mail,
+        registrar,
+    )
+}
+
+#[test]
+fn generates_account_without_runtime() {
+    let (generator, mail, registrar) = fake_generator();
+
+    let account = generator
+        .generate_with_name("S3cure-Password!", "Test User")
+        .unwrap();
+
+    assert_eq!(account.name, "Test User");
+    assert_eq!(registrar.verified(), vec![account.email.clone()]);
+    assert_eq!(mail.deleted(), vec![account.email]);
+}
+
+#[test]
+fn returns_the_same_errors() {
+    let (generator, _, _) = fake_generator();
+
+    let err = generator.generate("short").unwrap_err();
+    assert!(matches!(err, Error::WeakPassword(_)));
+
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    let err = generator
+        .generate_cancellable("S3cure-Password!", &cancel)
+        .unwrap_err();
+    assert!(matches!(err, Error::Cancelled { .. }));
+}
+
+#[tokio::test]
+async fn works_inside_current_thread_runtime() {
+    let (generator, _, registrar) = fake_generator();
+
+    let account = generator.generate("S3cure-Password!").unwrap();
+    assert!(generator.cleanup().is_empty());
+
+    assert_eq!(registrar.verified(), vec![account.email]);
+    // Dropping the generator, and with it its runtime, must not panic here either
+    drop(generator);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn works_inside_multi_thread_runtime() {
+    let (generator, _, registrar) = fake_generator();
+
+    let pending = generator
+        .start_registration("S3cure-Password!", None)
+        .unwrap();
+    let account = generator.resume(&pending, "S3cure-Password!").unwrap();
+
+    assert_eq!(registrar.verified(), vec![account.email]);
+    drop(generator);
+}
+
+#[tokio::test]
+async fn works_from_spawn_blocking() {
+    let (generator, _, registrar) = fake_generator();
+
+    let account = tokio::task::spawn_blocking(move || generator.generate("S3cure-Password!"))
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(registrar.verified(), vec![account.email]);
+}
@@ -0,0 +1,306 @@
+use base64::prelude::*;
+use meganz_account_generator::{
+    AccountGenerator, Error, FAKE_MEGA_SENDER, FakeMailProvider, FakeRegistrar, GeneratedAccount,
+    Result, Stage, SuspicionReason, Warning,
+};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Run a generation in which `from`, `subject` and `body` is the only message that ever arrives.
+///
+/// `{KEY}` in `body` is replaced with the confirmation key issued for the registration.
+async fn generate_with_message(
+    from: &str,
+    subject: &str,
+    body: &str,
+) -> (Result<GeneratedAccount>, FakeRegistrar) {
+    generate_with_body(from, subject, |key| body.replace("{KEY}", key)).await
+}
+
+/// Like [`generate_with_message`], but builds the body from the confirmation key with `make_body`.
+async fn generate_with_body(
+    from: &str,
+    subject: &str,
+    make_body: impl Fn(&str) -> String,
+) -> (Result<GeneratedAccount>, FakeRegistrar) {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::silent();
+    let generator = AccountGenerator::builder()
+        .timeout(Duration::from_millis(200))
+        .poll_interval(Duration::from_millis(10))
+        .build_with(mail.clone(), registrar.clone());
+
+    let run = generator.generate("S3cure-Password!");
+    let deliver = async {
+        loop {
+            if let Some(email) = mail.inboxes().pop() {
+                let body = make_body(&registrar.confirm_key(&email).unwrap());
+                mail.deliver(&email, from, subject, &body);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    };
+    let (result, ()) = tokio::join!(run, deliver);
+    (result, registrar)
+}
+
+fn assert_suspicious(result: Result<GeneratedAccount>, expected: SuspicionReason) {
+    let err = result.expect_err("expected suspicious email");
+    assert_eq!(err.stage(), Some(Stage::AwaitingConfirmation));
+    match err.root() {
+        Error::SuspiciousEmail { reason, .. } => assert_eq!(reason, &expected),
+        other => panic!("expected suspicious email ({expected}), got {other:?}"),
+    }
+}
+
+const VALID_BODY: &str = r#"<a href="https://mega.nz/#confirm{KEY}">Verify my email</a>"#;
+
+#[tokio::test]
+async fn accepts_display_name_sender_and_plain_text_link() {
+    let (result, registrar) = generate_with_message(
+        "MEGA <welcome@mega.nz>",
+        "MEGA email verification required",
+        "Click https://mega.nz/confirm{KEY}. Thanks!",
+    )
+    .await;
+
+    let account = result.unwrap();
+    assert_eq!(registrar.verified(), vec![account.email]);
+}
+
+#[tokio::test]
+async fn skips_spoofed_email_that_arrives_before_genuine_one() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::silent();
+    let generator = AccountGenerator::builder()
+        .timeout(Duration::from_secs(5))
+        .poll_interval(Duration::from_millis(10))
+        .build_with(mail.clone(), registrar.clone());
+
+    let run = generator.generate("S3cure-Password!");
+    let deliver = async {
+        let email = loop {
+            if let Some(email) = mail.inboxes().pop()
+                && registrar.confirm_key(&email).is_some()
+            {
+                break email;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        };
+        let spoofed = VALID_BODY.replace("{KEY}", "ForgedKey");
+        mail.deliver(
+            &email,
+            "MEGA <attacker@evil.example>",
+            "MEGA email verification required",
+            &spoofed,
+        );
+        // Let a few polls see only the forged message
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let genuine = VALID_BODY.replace("{KEY}", &registrar.confirm_key(&email).unwrap());
+        mail.deliver(
+            &email,
+            FAKE_MEGA_SENDER,
+            "MEGA email verification required",
+            &genuine,
+        );
+    };
+    let (result, ()) = tokio::join!(run, deliver);
+
+    let account = result.unwrap();
+    assert_eq!(registrar.verified(), vec![account.email.clone()]);
+    assert!(matches!(
+        account.report.warnings.as_slice(),
+        [Warning::SuspiciousEmailSkipped { from, .. }] if from == "MEGA <attacker@evil.example>"
+    ));
+}
+
+#[tokio::test]
+async fn rejects_lookalike_sender_domain() {
+    let (result, registrar) = generate_with_message(
+        "welcome@mega.nz.evil.example",
+        "MEGA email verification required",
+        VALID_BODY,
+    )
+    .await;
+
+    assert_suspicious(result, SuspicionReason::UntrustedSender);
+    assert!(registrar.verified().is_empty());
+}
+
+#[tokio::test]
+async fn rejects_mega_display_name_with_foreign_address() {
+    let (result, _) =
+        generate_with_message("MEGA <attacker@evil.example>", "Welcome", VALID_BODY).await;
+    assert_suspicious(result, SuspicionReason::UntrustedSender);
+}
+
+#[tokio::test]
+async fn rejects_mega_address_in_display_name() {
+    let (result, _) = generate_with_message(
+        "welcome@mega.nz <attacker@evil.example>",
+        "Welcome",
+        VALID_BODY,
+    )
+    .await;
+    assert_suspicious(result, SuspicionReason::UntrustedSender);
+}
+
+#[tokio::test]
+async fn rejects_mega_subject_from_foreign_sender() {
+    let (result, _) = generate_with_message(
+        "no-reply@evil.example",
+        "MEGA email verification required",
+        VALID_BODY,
+    )
+    .await;
+    assert_suspicious(result, SuspicionReason::UntrustedSender);
+}
+
+#[tokio::test]
+async fn rejects_lookalike_link_host() {
+    let (result, _) = generate_with_message(
+        "welcome@mega.nz",
+        "MEGA email verification required",
+        r#"<a href="https://mega.nz.evil.example/#confirm{KEY}">Verify</a>"#,
+    )
+    .await;
+    assert_suspicious(
+        result,
+        SuspicionReason::UntrustedLinkHost("mega.nz.evil.example".to_string()),
+    );
+}
+
+#[tokio::test]
+async fn rejects_userinfo_link_host_trick() {
+    let (result, _) = generate_with_message(
+        "welcome@mega.nz",
+        "MEGA email verification required",
+        r#"<a href="https://mega.nz@evil.example/#confirm{KEY}">Verify</a>"#,
+    )
+    .await;
+    assert_suspicious(
+        result,
+        SuspicionReason::UntrustedLinkHost("evil.example".to_string()),
+    );
+}
+
+#[tokio::test]
+async fn rejects_plain_http_link() {
+    let (result, _) = generate_with_message(
+        "welcome@mega.nz",
+        "MEGA email verification required",
+        r#"<a href="http://mega.nz/#confirm{KEY}">Verify</a>"#,
+    )
+    .await;
+    assert_suspicious(result, SuspicionReason::InsecureLink);
+}
+
+#[tokio::test]
+async fn rejects_key_with_foreign_characters() {
+    let (result, _) = generate_with_message(
+        "welcome@mega.nz",
+        "MEGA email verification required",
+        r#"<a href="https://mega.nz/#confirm{KEY}%22,%22a%22:%22up">Verify</a>"#,
+    )
+    .await;
+    assert_suspicious(result, SuspicionReason::MalformedKey);
+}
+
+#[tokio::test]
+async fn rejects_conflicting_keys() {
+    let (result, _) = generate_with_message(
+        "welcome@mega.nz",
+        "MEGA email verification required",
+        r#"<a href="https://mega.nz/#confirm{KEY}">Verify</a>
+           <a href="https://mega.nz/#confirmAttackerChosenKey">Verify</a>"#,
+    )
+    .await;
+    assert_suspicious(result, SuspicionReason::ConflictingKeys);
+}
+
+#[tokio::test]
+async fn ignores_unrelated_mail_with_foreign_links() {
+    let (result, registrar) = generate_with_message(
+        "spam@evil.example",
+        "You have won",
+        r#"<a href="https://evil.example/#confirm{KEY}">Claim</a>"#,
+    )
+    .await;
+
+    assert!(matches!(result.unwrap_err().root(), Error::EmailTimeout));
+    assert!(registrar.verified().is_empty());
+}
+
+/// `From` or `Subject` header of a fixture email.
+fn fixture_header(raw: &str, name: &str) -> String {
+    raw.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+        .unwrap_or_else(|| panic!("fixture has no {name} header"))
+        .trim()
+        .to_string()
+}
+
+#[tokio::test]
+async fn extracts_key_from_fixture_corpus() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/confirmation");
+    let mut fixtures: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "eml"))
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty(), "no fixtures in {}", dir.display());
+
+    for path in fixtures {
+        let raw = std::fs::read_to_string(&path).unwrap();
+        let from = fixture_header(&raw, "From");
+        let subject = fixture_header(&raw, "Subject");
+
+        let (result, registrar) = generate_with_message(&from, &subject, &raw).await;
+        match result {
+            Ok(account) => assert_eq!(registrar.verified(), vec![account.email]),
+            Err(e) => panic!("{}: {e}", path.display()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn extracts_key_from_base64_html_part() {
+    let (result, registrar) = generate_with_body(
+        "MEGA <welcome@mega.nz>",
+        "Verifica dell'indirizzo email MEGA richiesta",
+        |key| {
+            let html = format!(
+                "<p>Conferma il tuo indirizzo email:</p>\
+                 <p><a href=\"https://mega.nz/#confirm{key}\">Verifica la mia email</a></p>"
+            );
+            format!(
+                "MIME-Version: 1.0\r\n\
+                 Content-Type: text/html; charset=UTF-8\r\n\
+                 Content-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+                BASE64_STANDARD.encode(html)
+            )
+        },
+    )
+    .await;
+
+    let account = result.unwrap();
+    assert_eq!(registrar.verified(), vec![account.email]);
+}
+
+#[tokio::test]
+async fn rejects_tracking_redirect_to_untrusted_host() {
+    let (result, registrar) = generate_with_message(
+        "MEGA <welcome@mega.nz>",
+        "MEGA email verification required",
+        r#"<a href="https://click.mail.example/?upn=https%3A%2F%2Fevil.example%2F%23confirm{KEY}&amp;id=1">Verify</a>"#,
+    )
+    .await;
+
+    assert_suspicious(
+        result,
+        SuspicionReason::UntrustedLinkHost("evil.example".to_string()),
+    );
+    assert!(registrar.verified().is_empty());
+}
@@ -0,0 +1,134 @@
+use meganz_account_generator::SecretString;
+use meganz_account_generator::export::{EXPORT_SCHEMA_VERSION, ExportFormat, ExportRecord};
+
+fn render(format: ExportFormat, email: &str, password: &str, name: &str) -> String {
+    let password = SecretString::from(password);
+    let mut out = Vec::new();
+    format.write_header(&mut out).unwrap();
+    format
+        .write_record(
+            &mut out,
+            ExportRecord {
+                email,
+                password: &password,
+                name,
+            },
+        )
+        .unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn json_lines_escapes_quotes_and_control_characters() {
+    let line = render(
+        ExportFormat::JsonLines,
+        "user@example.com",
+        "p\"a\\s\ns\u{1}",
+        "Zoë",
+    );
+
+    assert_eq!(
+        line,
+        format!(
+            "{{\"schema_version\":{EXPORT_SCHEMA_VERSION},\"email\":\"user@example.com\",\
+             \"password\":\"p\\\"a\\\\s\\ns\\u0001\",\"name\":\"Zoë\"}}\n"
+        )
+    );
+}
+
+#[test]
+fn csv_quotes_fields_with_delimiters() {
+    let csv = render(
+        ExportFormat::Csv,
+        "user@example.com",
+        "pa,ss\"word",
+        "Line\nBreak",
+    );
+
+    assert_eq!(
+        csv,
+        format!(
+            "schema_version,email,password,name\r\n\
+             {EXPORT_SCHEMA_VERSION},user@example.com,\"pa,ss\"\"word\",\"Line\nBreak\"\r\n"
+        )
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_omits_secrets_and_requires_password_to_load() {
+    use meganz_account_generator::{
+        AccountSession, ExposeSecret, GeneratedAccount, GenerationReport, Warning,
+    };
+    use std::time::{Duration, SystemTime};
+
+    let started_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let report = GenerationReport {
+        started_at: Some(started_at),
+        verified_at: Some(started_at + Duration::from_secs(42)),
+        polls: 7,
+        inbox_deleted: Some(false),
+        warnings: vec![Warning::InboxNotDeleted {
+            email: "alias@sharklasers.com".to_string(),
+            reason: "server busy".to_string(),
+        }],
+        ..GenerationReport::default()
+    };
+    let account = GeneratedAccount {
+        email: "alias@sharklasers.com".to_string(),
+        password: "Password-Secret-1".into(),
+        name: "Automation Bot".to_string(),
+        session: Some(AccountSession {
+            session: "Session-Secret-2".into(),
+            storage_total: 20 << 30,
+            storage_used: 0,
+        }),
+        recovery_key: Some("Recovery-Secret-3".into()),
+        report: report.clone(),
+    };
+
+    let json = serde_json::to_string(&account).unwrap();
+    for secret in ["Password-Secret-1", "Session-Secret-2", "Recovery-Secret-3"] {
+        assert!(!json.contains(secret), "{secret} serialized: {json}");
+    }
+
+    // Serialized output has no password, so it does not load back as an account
+    assert!(serde_json::from_str::<GeneratedAccount>(&json).is_err());
+    let without_session = GeneratedAccount {
+        session: None,
+        ..account.clone()
+    };
+    let json_without_session = serde_json::to_string(&without_session).unwrap();
+    let err = serde_json::from_str::<GeneratedAccount>(&json_without_session).unwrap_err();
+    assert!(
+        err.to_string().contains("missing field `password`"),
+        "{err}"
+    );
+
+    // Secrets written by other tools are still loaded, and the rest of the account with them
+    let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    value["password"] = "Password-Secret-1".into();
+    value["session"]["session"] = "Session-Secret-2".into();
+    value["recovery_key"] = "Recovery-Secret-3".into();
+    let loaded: GeneratedAccount = serde_json::from_value(value.clone()).unwrap();
+    assert_eq!(loaded.email, account.email);
+    assert_eq!(loaded.name, account.name);
+    assert_eq!(loaded.report, report);
+    assert_eq!(loaded.password.expose_secret(), "Password-Secret-1");
+    assert_eq!(
+        loaded.recovery_key.unwrap().expose_secret(),
+        "Recovery-Secret-3"
+    );
+    let session = loaded.session.unwrap();
+    assert_eq!(session.session.expose_secret(), "Session-Secret-2");
+    assert_eq!(session.storage_total, 20 << 30);
+
+    // A session without its token is rejected rather than loaded as empty
+    value["session"].as_object_mut().unwrap().remove("session");
+    let err = serde_json::from_value::<GeneratedAccount>(value).unwrap_err();
+    assert!(err.to_string().contains("missing field `session`"), "{err}");
+
+    let report_json = serde_json::to_string(&report).unwrap();
+    let restored: GenerationReport = serde_json::from_str(&report_json).unwrap();
+    assert_eq!(restored, report);
+}
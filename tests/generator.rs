@@ -0,0 +1,1176 @@
+use megalib::MegaError;
+use meganz_account_generator::{
+    AccountGenerator, AccountSession, CancellationToken, Error, ErrorKind, ExposeSecret,
+    FAKE_MEGA_SENDER, FakeMailProvider, FakeRegistrar, InboxMessage, MailProvider, PasswordPolicy,
+    PasswordWeakness, PendingRegistration, ProgressEvent, Registrar, RegistrationState, Result,
+    Stage, Warning,
+};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn fake_generator(
+    mail: &FakeMailProvider,
+    registrar: &FakeRegistrar,
+) -> AccountGenerator<FakeMailProvider, FakeRegistrar> {
+    AccountGenerator::builder()
+        .timeout(Duration::from_millis(200))
+        .poll_interval(Duration::from_millis(10))
+        .build_with(mail.clone(), registrar.clone())
+}
+
+#[tokio::test]
+async fn generates_account_without_network() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let generator = fake_generator(&mail, &registrar);
+
+    let account = generator
+        .generate_with_name("S3cure-Password!", "Test User")
+        .await
+        .unwrap();
+
+    assert!(account.email.ends_with(FakeMailProvider::DOMAIN));
+    assert_eq!(account.name, "Test User");
+    assert!(account.session.is_none());
+    assert_eq!(registrar.verified(), vec![account.email.clone()]);
+    assert_eq!(mail.deleted(), vec![account.email]);
+}
+
+#[tokio::test]
+async fn reports_timings_and_inbox_deletion() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let generator = fake_generator(&mail, &registrar);
+
+    let account = generator.generate("S3cure-Password!").await.unwrap();
+
+    let report = &account.report;
+    let steps = [
+        report.started_at,
+        report.inbox_created_at,
+        report.registered_at,
+        report.first_mega_email_at,
+        report.verified_at,
+        report.cleaned_up_at,
+    ];
+    assert!(steps.iter().all(Option::is_some), "{report:?}");
+    assert!(steps.is_sorted());
+    assert_eq!(report.polls, 1);
+    assert_eq!(report.inbox_deleted, Some(true));
+    assert!(report.duration().is_some());
+}
+
+#[tokio::test]
+async fn redacts_password_when_formatting_account() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let generator = fake_generator(&mail, &registrar);
+
+    let account = generator.generate("S3cure-Password!").await.unwrap();
+
+    assert_eq!(account.password.expose_secret(), "S3cure-Password!");
+    assert!(!format!("{account:?}").contains("S3cure-Password!"));
+    assert!(!account.to_string().contains("S3cure-Password!"));
+}
+
+/// Subscriber that writes every span field and event field this crate records into one string.
+#[derive(Clone, Default)]
+struct RecordingSubscriber {
+    log: Arc<Mutex<String>>,
+    next_id: Arc<std::sync::atomic::AtomicU64>,
+}
+
+impl tracing::field::Visit for RecordingSubscriber {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        let line = format!("{}={value:?}\n", field.name());
+        self.log.lock().unwrap().push_str(&line);
+    }
+}
+
+impl tracing::Subscriber for RecordingSubscriber {
+    fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        metadata.target().starts_with("meganz_account_generator")
+    }
+
+    fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        let line = format!("span {}\n", span.metadata().name());
+        self.log.lock().unwrap().push_str(&line);
+        span.record(&mut self.clone());
+        let id = self
+            .next_id
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        tracing::span::Id::from_u64(id + 1)
+    }
+
+    fn record(&self, _: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+        values.record(&mut self.clone());
+    }
+
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        event.record(&mut self.clone());
+    }
+
+    fn enter(&self, _: &tracing::span::Id) {}
+
+    fn exit(&self, _: &tracing::span::Id) {}
+}
+
+#[tokio::test]
+async fn tracing_output_never_contains_secrets() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let generator = AccountGenerator::builder()
+        .poll_interval(Duration::from_millis(10))
+        .login_check(true)
+        .export_recovery_key(true)
+        .build_with(mail.clone(), registrar.clone());
+    let subscriber = RecordingSubscriber::default();
+
+    let _guard = tracing::subscriber::set_default(subscriber.clone());
+    let account = generator.generate("S3cure-Password!").await.unwrap();
+
+    let log = subscriber.log.lock().unwrap();
+    assert!(log.contains("span stage"));
+    assert!(log.contains("span poll"));
+    assert!(log.contains(&format!("email={:?}", account.email)));
+    assert!(log.contains("messages=1"));
+    assert!(log.contains("subject="));
+    for secret in [
+        "S3cure-Password!",
+        "FakeConfirmKey",
+        "FakeSession",
+        "FakeRecoveryKey",
+    ] {
+        assert!(!log.contains(secret), "{secret} leaked into tracing output");
+    }
+}
+
+/// Registrar whose login fails with a transport error on a request that carries a session ID.
+#[derive(Clone)]
+struct UnreachableSession(FakeRegistrar);
+
+impl UnreachableSession {
+    const SESSION_ID: &str = "LiveSessionId";
+}
+
+impl Registrar for UnreachableSession {
+    async fn register(&self, email: &str, password: &str, name: &str) -> Result<RegistrationState> {
+        self.0.register(email, password, name).await
+    }
+
+    async fn verify(&self, state: &RegistrationState, confirm_key: &str) -> Result<()> {
+        self.0.verify(state, confirm_key).await
+    }
+
+    async fn login(&self, _: &str, _: &str) -> Result<AccountSession> {
+        // Nothing listens on the discard port, so the request fails before leaving the machine
+        let mut api = megalib::api::ApiClient::with_proxy("http://127.0.0.1:9")?;
+        api.set_session_id(Self::SESSION_ID.to_string());
+        api.request(serde_json::json!({"a": "uq"})).await?;
+        unreachable!("the proxy refuses connections")
+    }
+}
+
+#[tokio::test]
+async fn tracing_output_omits_session_ids_from_failed_requests() {
+    let mail = FakeMailProvider::new();
+    let registrar = UnreachableSession(FakeRegistrar::new(mail.clone()));
+    let generator = AccountGenerator::builder()
+        .poll_interval(Duration::from_millis(10))
+        .login_check(true)
+        .build_with(mail, registrar);
+    let subscriber = RecordingSubscriber::default();
+
+    let _guard = tracing::subscriber::set_default(subscriber.clone());
+    let result = generator.generate("S3cure-Password!").await;
+
+    let log = subscriber.log.lock().unwrap();
+    assert!(log.contains("Request error"), "login did not fail: {log}");
+    assert!(
+        !log.contains("sid="),
+        "session ID leaked into tracing output: {log}"
+    );
+    assert!(!log.contains(UnreachableSession::SESSION_ID));
+    if let Err(e) = result {
+        assert!(!e.to_string().contains(UnreachableSession::SESSION_ID));
+    }
+}
+
+#[tokio::test]
+async fn reports_progress_for_each_stage() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    let generator = AccountGenerator::builder()
+        .poll_interval(Duration::from_millis(10))
+        .on_progress(move |event| sink.lock().unwrap().push(event.clone()))
+        .build_with(mail, registrar);
+
+    let account = generator.generate("S3cure-Password!").await.unwrap();
+
+    let email = account.email;
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            ProgressEvent::InboxCreated {
+                email: email.clone()
+            },
+            ProgressEvent::RegistrationSubmitted {
+                email: email.clone()
+            },
+            ProgressEvent::Polled {
+                attempt: 1,
+                messages: 1
+            },
+            ProgressEvent::MegaEmailSeen {
+                from: FAKE_MEGA_SENDER.to_string(),
+                subject: "MEGA email verification required".to_string(),
+            },
+            ProgressEvent::ConfirmKeyExtracted,
+            ProgressEvent::Verified {
+                email: email.clone()
+            },
+            ProgressEvent::InboxDeleted {
+                email,
+                deleted: true
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn times_out_when_no_email_arrives() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::silent();
+    let generator = fake_generator(&mail, &registrar);
+
+    let err = generator.generate("S3cure-Password!").await.unwrap_err();
+
+    assert!(matches!(err.root(), Error::EmailTimeout));
+    assert!(registrar.verified().is_empty());
+    assert_eq!(mail.deleted(), vec![err.email().unwrap().to_string()]);
+    assert!(generator.leftover_inboxes().is_empty());
+}
+
+#[tokio::test]
+async fn reports_missing_link_in_mega_email() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::silent();
+    let generator = fake_generator(&mail, &registrar);
+
+    let run = generator.generate("S3cure-Password!");
+    let deliver = async {
+        loop {
+            if let Some(email) = mail.inboxes().pop() {
+                mail.deliver(&email, FAKE_MEGA_SENDER, "Welcome to MEGA", "No link here.");
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    };
+    let (result, ()) = tokio::join!(run, deliver);
+
+    assert!(matches!(
+        result.unwrap_err().root(),
+        Error::NoConfirmationLink
+    ));
+}
+
+#[tokio::test]
+async fn cancellation_stops_polling_and_deletes_inbox() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::silent();
+    let generator = AccountGenerator::builder()
+        .timeout(Duration::from_secs(60))
+        .poll_interval(Duration::from_secs(60))
+        .build_with(mail.clone(), registrar);
+    let cancel = CancellationToken::new();
+
+    let run = generator.generate_cancellable("S3cure-Password!", &cancel);
+    let trigger = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancel.cancel();
+    };
+    let (result, ()) =
+        tokio::time::timeout(Duration::from_secs(5), async { tokio::join!(run, trigger) })
+            .await
+            .expect("cancellation was not observed promptly");
+
+    match result {
+        Err(Error::Cancelled {
+            stage: Stage::AwaitingConfirmation,
+            email: Some(email),
+        }) => assert_eq!(mail.deleted(), vec![email]),
+        other => panic!("expected cancellation while polling, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn resumes_saved_registration_in_new_generator() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+
+    let pending = fake_generator(&mail, &registrar)
+        .start_registration("S3cure-Password!", Some("Test User"))
+        .await
+        .unwrap();
+    let saved = pending.serialize();
+    assert!(!format!("{pending:?}").contains(&pending.state.serialize()));
+
+    let restored = PendingRegistration::deserialize(&saved).unwrap();
+    let account = fake_generator(&mail, &registrar)
+        .resume(&restored, "S3cure-Password!")
+        .await
+        .unwrap();
+
+    assert_eq!(account.email, pending.email);
+    assert_eq!(account.name, "Test User");
+    assert_eq!(registrar.verified(), vec![account.email.clone()]);
+    assert_eq!(mail.deleted(), vec![account.email]);
+}
+
+#[tokio::test]
+async fn resume_reattaches_saved_inbox() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let pending = fake_generator(&mail, &registrar)
+        .start_registration("S3cure-Password!", None)
+        .await
+        .unwrap();
+    // Another registration moves the shared mail session to a new inbox
+    mail.create_email("someoneelse").await.unwrap();
+
+    let account = fake_generator(&mail, &registrar)
+        .resume(&pending, "S3cure-Password!")
+        .await
+        .unwrap();
+
+    assert_eq!(account.email, pending.email);
+    assert_eq!(mail.current(), Some(pending.email));
+}
+
+#[tokio::test]
+async fn resume_deletes_inbox_after_deadline() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::silent();
+    let generator = fake_generator(&mail, &registrar);
+
+    // No confirmation email arrives, so polling runs until the deadline
+    let pending = generator
+        .start_registration("S3cure-Password!", None)
+        .await
+        .unwrap();
+    let err = generator
+        .resume(&pending, "S3cure-Password!")
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err.root(), Error::EmailTimeout));
+    assert_eq!(mail.deleted(), vec![pending.email]);
+    assert!(generator.leftover_inboxes().is_empty());
+}
+
+#[tokio::test]
+async fn interrupted_resume_keeps_inbox_for_another_attempt() {
+    let mail = FakeMailProvider::new();
+    // Nothing arrives until the confirmation email is delivered below
+    let registrar = FakeRegistrar::silent();
+    let generator = AccountGenerator::builder()
+        .timeout(Duration::from_secs(60))
+        .poll_interval(Duration::from_millis(10))
+        .build_with(mail.clone(), registrar.clone());
+    let pending = generator
+        .start_registration("S3cure-Password!", None)
+        .await
+        .unwrap();
+
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    let err = generator
+        .resume_cancellable(&pending, "S3cure-Password!", &cancel)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Cancelled { .. }));
+
+    let err = AccountGenerator::builder()
+        .total_timeout(Duration::from_millis(50))
+        .build_with(mail.clone(), registrar.clone())
+        .resume(&pending, "S3cure-Password!")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::StageTimeout { .. }));
+
+    assert!(mail.deleted().is_empty());
+    assert!(generator.leftover_inboxes().is_empty());
+
+    let key = registrar.confirm_key(&pending.email).unwrap();
+    mail.deliver(
+        &pending.email,
+        FAKE_MEGA_SENDER,
+        "MEGA email verification required",
+        &format!("<a href=\"https://mega.nz/#confirm{key}\">Verify my email</a>"),
+    );
+    let account = generator
+        .resume(&pending, "S3cure-Password!")
+        .await
+        .unwrap();
+    assert_eq!(registrar.verified(), vec![account.email.clone()]);
+    assert_eq!(mail.deleted(), vec![account.email]);
+}
+
+#[tokio::test]
+async fn confirms_existing_address_with_pasted_link() {
+    let registrar = FakeRegistrar::silent();
+    let generator = AccountGenerator::builder().build_with((), registrar.clone());
+
+    let pending = generator
+        .register_existing("me@example.com", "S3cure-Password!", Some("Test User"))
+        .await
+        .unwrap();
+    let key = registrar.confirm_key("me@example.com").unwrap();
+
+    let err = generator
+        .confirm_manually(
+            &pending,
+            "S3cure-Password!",
+            &format!("https://mega.nz.evil.test/#confirm{}", key),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidConfirmationLink(_)));
+    assert!(matches!(
+        generator
+            .confirm_manually(&pending, "S3cure-Password!", "  ")
+            .await,
+        Err(Error::NoConfirmationLink)
+    ));
+
+    let account = generator
+        .confirm_manually(
+            &pending,
+            "S3cure-Password!",
+            &format!(" https://mega.nz/#confirm{}\n", key),
+        )
+        .await
+        .unwrap();
+    assert_eq!(account.email, "me@example.com");
+    assert_eq!(account.name, "Test User");
+    assert_eq!(registrar.verified(), vec!["me@example.com".to_string()]);
+}
+
+/// Mail provider whose inbox checks never complete.
+#[derive(Clone)]
+struct HangingMail(FakeMailProvider);
+
+impl MailProvider for HangingMail {
+    async fn create_email(&self, alias: &str) -> Result<String> {
+        self.0.create_email(alias).await
+    }
+
+    async fn attach(&self, email: &str) -> Result<()> {
+        self.0.attach(email).await
+    }
+
+    async fn get_messages(&self, _email: &str) -> Result<Vec<InboxMessage>> {
+        std::future::pending().await
+    }
+
+    async fn fetch_body(&self, email: &str, id: &str) -> Result<String> {
+        self.0.fetch_body(email, id).await
+    }
+
+    async fn delete_email(&self, email: &str) -> Result<bool> {
+        self.0.delete_email(email).await
+    }
+}
+
+/// Mail provider whose inboxes cannot be deleted.
+#[derive(Clone)]
+struct UndeletableMail(FakeMailProvider);
+
+impl MailProvider for UndeletableMail {
+    async fn create_email(&self, alias: &str) -> Result<String> {
+        self.0.create_email(alias).await
+    }
+
+    async fn attach(&self, email: &str) -> Result<()> {
+        self.0.attach(email).await
+    }
+
+    async fn get_messages(&self, email: &str) -> Result<Vec<InboxMessage>> {
+        self.0.get_messages(email).await
+    }
+
+    async fn fetch_body(&self, email: &str, id: &str) -> Result<String> {
+        self.0.fetch_body(email, id).await
+    }
+
+    async fn delete_email(&self, _email: &str) -> Result<bool> {
+        Err(MegaError::Custom("delete failed".to_string()).into())
+    }
+}
+
+#[tokio::test]
+async fn report_records_failed_inbox_deletion() {
+    let mail = FakeMailProvider::new();
+    let generator = AccountGenerator::builder()
+        .poll_interval(Duration::from_millis(10))
+        .build_with(UndeletableMail(mail.clone()), FakeRegistrar::new(mail));
+
+    let account = generator.generate("S3cure-Password!").await.unwrap();
+
+    assert_eq!(account.report.inbox_deleted, Some(false));
+    assert!(account.report.cleaned_up_at.is_some());
+    assert!(matches!(
+        account.report.warnings.as_slice(),
+        [Warning::InboxNotDeleted { email, .. }] if *email == account.email
+    ));
+    assert_eq!(generator.leftover_inboxes(), vec![account.email.clone()]);
+
+    // Deletion keeps failing, so the inbox stays listed
+    let warnings = generator.cleanup().await;
+    assert_eq!(warnings, account.report.warnings);
+    assert_eq!(generator.leftover_inboxes(), vec![account.email]);
+}
+
+#[tokio::test]
+async fn cleanup_deletes_inbox_of_dropped_run() {
+    let mail = FakeMailProvider::new();
+    let generator = AccountGenerator::builder()
+        .poll_interval(Duration::from_millis(10))
+        .build_with(HangingMail(mail.clone()), FakeRegistrar::silent());
+
+    // Dropping the future skips the cleanup the run would have done itself
+    let dropped = tokio::time::timeout(
+        Duration::from_millis(50),
+        generator.generate("S3cure-Password!"),
+    )
+    .await;
+    assert!(dropped.is_err());
+    assert!(mail.deleted().is_empty());
+    let leftovers = generator.leftover_inboxes();
+    assert_eq!(leftovers.len(), 1);
+
+    assert!(generator.cleanup().await.is_empty());
+    assert_eq!(mail.deleted(), leftovers);
+    assert!(generator.leftover_inboxes().is_empty());
+}
+
+/// Registrar whose verification never completes.
+#[derive(Clone)]
+struct HangingVerify(FakeRegistrar);
+
+impl Registrar for HangingVerify {
+    async fn register(&self, email: &str, password: &str, name: &str) -> Result<RegistrationState> {
+        self.0.register(email, password, name).await
+    }
+
+    async fn verify(&self, _state: &RegistrationState, _confirm_key: &str) -> Result<()> {
+        std::future::pending().await
+    }
+}
+
+#[tokio::test]
+async fn timeout_abandons_hung_inbox_check() {
+    let mail = FakeMailProvider::new();
+    let generator = AccountGenerator::builder()
+        .timeout(Duration::from_millis(50))
+        .poll_interval(Duration::from_millis(10))
+        .build_with(HangingMail(mail), FakeRegistrar::silent());
+
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        generator.generate("S3cure-Password!"),
+    )
+    .await
+    .expect("generation ran past its timeout");
+
+    assert!(matches!(result.unwrap_err().root(), Error::EmailTimeout));
+}
+
+#[tokio::test]
+async fn total_timeout_names_running_stage() {
+    let mail = FakeMailProvider::new();
+    let generator = AccountGenerator::builder()
+        .timeout(Duration::from_secs(60))
+        .total_timeout(Duration::from_millis(50))
+        .build_with(HangingMail(mail), FakeRegistrar::silent());
+
+    let err = generator.generate("S3cure-Password!").await.unwrap_err();
+
+    match err {
+        Error::StageTimeout { stage, email } => {
+            assert_eq!(stage, Stage::AwaitingConfirmation);
+            assert!(email.unwrap().ends_with(FakeMailProvider::DOMAIN));
+        }
+        other => panic!("expected stage timeout, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn stage_timeout_bounds_verification() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let generator = AccountGenerator::builder()
+        .poll_interval(Duration::from_millis(10))
+        .stage_timeout(Stage::Verifying, Duration::from_millis(50))
+        .build_with(mail, HangingVerify(registrar.clone()));
+
+    let err = generator.generate("S3cure-Password!").await.unwrap_err();
+
+    assert_eq!(err.stage(), Some(Stage::Verifying));
+    assert_eq!(err.kind(), ErrorKind::Timeout);
+    let Error::VerificationFailed {
+        source, attempts, ..
+    } = err.root()
+    else {
+        panic!("expected verification failure, got {err:?}");
+    };
+    assert!(matches!(
+        **source,
+        Error::StageTimeout {
+            stage: Stage::Verifying,
+            ..
+        }
+    ));
+    assert_eq!(*attempts, 1);
+    assert!(registrar.verified().is_empty());
+}
+
+#[tokio::test]
+async fn classifies_failures_by_stage() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::silent();
+    let generator = fake_generator(&mail, &registrar);
+
+    let err = generator.generate("S3cure-Password!").await.unwrap_err();
+    assert_eq!(err.stage(), Some(Stage::AwaitingConfirmation));
+    assert!(err.email().unwrap().ends_with(FakeMailProvider::DOMAIN));
+    assert_eq!(err.kind(), ErrorKind::Timeout);
+    assert!(err.is_retryable());
+    assert!(err.leaves_unconfirmed_account());
+
+    let pending = generator
+        .register_existing("me@example.com", "S3cure-Password!", None)
+        .await
+        .unwrap();
+    let err = generator
+        .confirm_manually(&pending, "S3cure-Password!", "WrongKey")
+        .await
+        .unwrap_err();
+    assert_eq!(err.stage(), Some(Stage::Verifying));
+    assert_eq!(err.email(), Some("me@example.com"));
+    assert!(matches!(err.root(), Error::VerificationFailed { .. }));
+    assert_eq!(err.kind(), ErrorKind::Rejected);
+    assert!(!err.is_retryable());
+}
+
+/// Registrar whose first `failures` verifications fail with a retryable error.
+#[derive(Clone)]
+struct FlakyVerify {
+    inner: FakeRegistrar,
+    failures: Arc<Mutex<u32>>,
+}
+
+impl FlakyVerify {
+    fn new(inner: FakeRegistrar, failures: u32) -> Self {
+        Self {
+            inner,
+            failures: Arc::new(Mutex::new(failures)),
+        }
+    }
+}
+
+impl Registrar for FlakyVerify {
+    async fn register(&self, email: &str, password: &str, name: &str) -> Result<RegistrationState> {
+        self.inner.register(email, password, name).await
+    }
+
+    async fn verify(&self, state: &RegistrationState, confirm_key: &str) -> Result<()> {
+        {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(MegaError::ServerBusy.into());
+            }
+        }
+        self.inner.verify(state, confirm_key).await
+    }
+}
+
+#[tokio::test]
+async fn retries_transient_verification_failures() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let generator = AccountGenerator::builder()
+        .poll_interval(Duration::from_millis(10))
+        .retry_backoff(Duration::from_millis(1))
+        .on_progress({
+            let events = events.clone();
+            move |event| events.lock().unwrap().push(event.clone())
+        })
+        .build_with(mail, FlakyVerify::new(registrar.clone(), 2));
+
+    let account = generator.generate("S3cure-Password!").await.unwrap();
+
+    assert_eq!(registrar.verified(), vec![account.email]);
+    let retries: Vec<_> = events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|event| match event {
+            ProgressEvent::RetryScheduled { stage, attempt, .. } => Some((*stage, *attempt)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(retries, vec![(Stage::Verifying, 1), (Stage::Verifying, 2)]);
+}
+
+#[tokio::test]
+async fn exhausted_retries_hand_back_confirm_key() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let generator = AccountGenerator::builder()
+        .poll_interval(Duration::from_millis(10))
+        .max_retries(1)
+        .retry_backoff(Duration::from_millis(1))
+        .build_with(mail, FlakyVerify::new(registrar.clone(), 2));
+
+    let err = generator.generate("S3cure-Password!").await.unwrap_err();
+    assert!(err.is_retryable());
+    let Error::VerificationFailed {
+        pending,
+        confirm_key,
+        attempts,
+        ..
+    } = err.root()
+    else {
+        panic!("expected verification failure, got {err:?}");
+    };
+    assert_eq!(*attempts, 2);
+    assert!(registrar.verified().is_empty());
+
+    // The flaky registrar has recovered; the handed-back key confirms the same registration
+    let account = generator
+        .confirm_manually(pending, "S3cure-Password!", confirm_key.expose_secret())
+        .await
+        .unwrap();
+    assert_eq!(registrar.verified(), vec![account.email]);
+}
+
+#[tokio::test]
+async fn stage_timeout_during_retry_backoff_hands_back_confirm_key() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let generator = AccountGenerator::builder()
+        .poll_interval(Duration::from_millis(10))
+        .max_retries(5)
+        .retry_backoff(Duration::from_secs(60))
+        .stage_timeout(Stage::Verifying, Duration::from_millis(50))
+        .build_with(mail.clone(), FlakyVerify::new(registrar.clone(), u32::MAX));
+
+    let err = generator.generate("S3cure-Password!").await.unwrap_err();
+    let Error::VerificationFailed {
+        pending,
+        confirm_key,
+        attempts,
+        source,
+    } = err.root()
+    else {
+        panic!("expected verification failure, got {err:?}");
+    };
+    assert!(matches!(**source, Error::StageTimeout { .. }));
+    assert_eq!(*attempts, 1);
+    assert!(registrar.verified().is_empty());
+
+    let generator = fake_generator(&mail, &registrar);
+    let account = generator
+        .confirm_manually(pending, "S3cure-Password!", confirm_key.expose_secret())
+        .await
+        .unwrap();
+    assert_eq!(registrar.verified(), vec![account.email]);
+}
+
+#[tokio::test]
+async fn logs_in_after_confirmation_when_enabled() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let generator = AccountGenerator::builder()
+        .poll_interval(Duration::from_millis(10))
+        .login_check(true)
+        .on_progress({
+            let events = events.clone();
+            move |event| events.lock().unwrap().push(event.clone())
+        })
+        .build_with(mail.clone(), registrar);
+
+    let account = generator.generate("S3cure-Password!").await.unwrap();
+
+    let session = account
+        .session
+        .expect("login check should return a session");
+    assert!(!session.session.expose_secret().is_empty());
+    assert_eq!(session.storage_total, FakeRegistrar::STORAGE_TOTAL);
+    assert_eq!(session.storage_used, 0);
+    assert!(events.lock().unwrap().contains(&ProgressEvent::LoggedIn {
+        email: account.email.clone(),
+        storage_total: FakeRegistrar::STORAGE_TOTAL,
+        storage_used: 0,
+    }));
+    assert_eq!(mail.deleted(), vec![account.email]);
+}
+
+#[tokio::test]
+async fn exports_recovery_key_when_enabled() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let generator = AccountGenerator::builder()
+        .poll_interval(Duration::from_millis(10))
+        .export_recovery_key(true)
+        .on_progress({
+            let events = events.clone();
+            move |event| events.lock().unwrap().push(event.clone())
+        })
+        .build_with(mail.clone(), registrar.clone());
+
+    let account = generator.generate("S3cure-Password!").await.unwrap();
+
+    let recovery_key = account
+        .recovery_key
+        .as_ref()
+        .expect("export should return a recovery key");
+    let expected = registrar
+        .recovery_key(&account.email, "S3cure-Password!")
+        .await
+        .unwrap();
+    assert_eq!(recovery_key.expose_secret(), expected.expose_secret());
+    assert!(account.session.is_none());
+    assert!(
+        events
+            .lock()
+            .unwrap()
+            .contains(&ProgressEvent::RecoveryKeyExported {
+                email: account.email.clone(),
+            })
+    );
+}
+
+#[tokio::test]
+async fn failed_login_check_still_returns_account() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    // `FlakyVerify` does not implement `login`, so the default "not supported" error is returned
+    let generator = AccountGenerator::builder()
+        .poll_interval(Duration::from_millis(10))
+        .login_check(true)
+        .build_with(mail.clone(), FlakyVerify::new(registrar.clone(), 0));
+
+    let account = generator.generate("S3cure-Password!").await.unwrap();
+
+    assert_eq!(account.password.expose_secret(), "S3cure-Password!");
+    assert!(account.session.is_none());
+    assert!(matches!(
+        account.report.warnings.as_slice(),
+        [Warning::LoginFailed { email, .. }] if *email == account.email
+    ));
+    assert_eq!(registrar.verified(), vec![account.email.clone()]);
+    assert_eq!(mail.deleted(), vec![account.email]);
+}
+
+#[tokio::test]
+async fn failed_recovery_key_export_still_returns_account() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    // `FlakyVerify` does not implement `recovery_key` either
+    let generator = AccountGenerator::builder()
+        .poll_interval(Duration::from_millis(10))
+        .export_recovery_key(true)
+        .build_with(mail.clone(), FlakyVerify::new(registrar.clone(), 0));
+
+    let account = generator.generate("S3cure-Password!").await.unwrap();
+
+    assert_eq!(account.password.expose_secret(), "S3cure-Password!");
+    assert!(account.recovery_key.is_none());
+    assert!(matches!(
+        account.report.warnings.as_slice(),
+        [Warning::RecoveryKeyNotExported { email, .. }] if *email == account.email
+    ));
+    assert_eq!(registrar.verified(), vec![account.email.clone()]);
+    assert_eq!(mail.deleted(), vec![account.email]);
+}
+
+#[tokio::test]
+async fn changes_email_through_inbox_polling() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let account = fake_generator(&mail, &registrar)
+        .generate("S3cure-Password!")
+        .await
+        .unwrap();
+
+    // The new address is read through the provider passed in, not the generator's own
+    let generator = fake_generator(&FakeMailProvider::new(), &registrar);
+    let moved = generator
+        .change_email(&account, "owned@example.com", &mail)
+        .await
+        .unwrap();
+
+    assert_eq!(moved.email, "owned@example.com");
+    assert_eq!(registrar.email_change_key("owned@example.com"), None);
+    // The account now logs in under its new address only
+    assert!(
+        registrar
+            .login("owned@example.com", "S3cure-Password!")
+            .await
+            .is_ok()
+    );
+    assert!(
+        registrar
+            .login(&account.email, "S3cure-Password!")
+            .await
+            .is_err()
+    );
+    // The new inbox is ours to keep
+    assert!(mail.inboxes().contains(&"owned@example.com".to_string()));
+}
+
+#[tokio::test]
+async fn confirms_email_change_with_pasted_link() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let generator = fake_generator(&mail, &registrar);
+    let account = generator.generate("S3cure-Password!").await.unwrap();
+
+    generator
+        .request_email_change(&account, "owned@example.com")
+        .await
+        .unwrap();
+    let key = registrar.email_change_key("owned@example.com").unwrap();
+
+    // A signup link is not an email change link
+    let err = generator
+        .confirm_email_change(
+            &account,
+            "owned@example.com",
+            &format!("https://mega.nz/#confirm{}", key),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::NoConfirmationLink));
+
+    let moved = generator
+        .confirm_email_change(
+            &account,
+            "owned@example.com",
+            &format!("https://mega.nz/#verify{}", key),
+        )
+        .await
+        .unwrap();
+    assert_eq!(moved.email, "owned@example.com");
+}
+
+#[tokio::test]
+async fn email_change_failures_name_their_stage() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let generator = fake_generator(&mail, &registrar);
+    let mut account = generator.generate("S3cure-Password!").await.unwrap();
+    account.password = "wrong-password".into();
+
+    let err = generator
+        .change_email(&account, "owned@example.com", &mail)
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.stage(), Some(Stage::ChangingEmail));
+    assert!(matches!(err.root(), Error::Mega(_)));
+    assert!(!err.leaves_unconfirmed_account());
+}
+
+#[tokio::test]
+async fn cancels_account_through_inbox_polling() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let generator = AccountGenerator::builder()
+        .timeout(Duration::from_millis(200))
+        .poll_interval(Duration::from_millis(10))
+        .on_progress({
+            let events = events.clone();
+            move |event| events.lock().unwrap().push(event.clone())
+        })
+        .build_with(mail.clone(), registrar.clone());
+    let account = generator.generate("S3cure-Password!").await.unwrap();
+
+    generator.cancel_account(&account).await.unwrap();
+
+    assert_eq!(registrar.cancelled(), vec![account.email.clone()]);
+    assert!(
+        registrar
+            .login(&account.email, "S3cure-Password!")
+            .await
+            .is_err()
+    );
+    assert!(
+        events
+            .lock()
+            .unwrap()
+            .contains(&ProgressEvent::AccountCancelled {
+                email: account.email.clone(),
+            })
+    );
+}
+
+#[tokio::test]
+async fn cancellation_reattaches_account_inbox() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let account = fake_generator(&mail, &registrar)
+        .generate("S3cure-Password!")
+        .await
+        .unwrap();
+    // A later run leaves the shared mail session on another inbox
+    mail.create_email("someoneelse").await.unwrap();
+    assert_ne!(mail.current(), Some(account.email.clone()));
+
+    fake_generator(&mail, &registrar)
+        .cancel_account(&account)
+        .await
+        .unwrap();
+
+    assert_eq!(registrar.cancelled(), vec![account.email]);
+}
+
+#[tokio::test]
+async fn cancellation_ignores_pages_named_like_the_action() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let generator = fake_generator(&mail, &registrar);
+    let account = generator.generate("S3cure-Password!").await.unwrap();
+    mail.deliver(
+        &account.email,
+        "MEGA <support@mega.nz>",
+        "About your MEGA account",
+        r#"<a href="https://mega.nz/cancellation-policy">Cancellation policy</a>
+           <a href="https://mega.nz/#cancellation-faq">FAQ</a>"#,
+    );
+
+    generator.cancel_account(&account).await.unwrap();
+
+    assert_eq!(registrar.cancelled(), vec![account.email]);
+}
+
+#[tokio::test]
+async fn cancellation_ignores_confirmation_links() {
+    let mail = FakeMailProvider::new();
+    // A silent registrar never sends the cancellation email, leaving only the signup confirmation
+    let registrar = FakeRegistrar::silent();
+    let generator = fake_generator(&mail, &registrar);
+    let pending = generator
+        .register_existing("owned@example.com", "S3cure-Password!", None)
+        .await
+        .unwrap();
+    let key = registrar.confirm_key("owned@example.com").unwrap();
+    let account = generator
+        .confirm_manually(&pending, "S3cure-Password!", &key)
+        .await
+        .unwrap();
+    mail.deliver(
+        &account.email,
+        FAKE_MEGA_SENDER,
+        "MEGA email verification required",
+        &format!("<a href=\"https://mega.nz/#confirm{}\">Verify</a>", key),
+    );
+
+    let err = generator.cancel_account(&account).await.unwrap_err();
+
+    assert_eq!(err.stage(), Some(Stage::CancellingAccount));
+    assert!(matches!(err.root(), Error::NoConfirmationLink));
+    assert!(registrar.cancelled().is_empty());
+}
+
+#[tokio::test]
+async fn weak_password_fails_before_creating_inbox() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::new(mail.clone());
+    let generator = AccountGenerator::builder()
+        .password_policy(PasswordPolicy::new().breached_passwords(["S3cure-Password!"]))
+        .build_with(mail.clone(), registrar.clone());
+
+    let err = generator.generate("S3cure-Password!").await.unwrap_err();
+
+    assert!(matches!(
+        err,
+        Error::WeakPassword(PasswordWeakness::Breached)
+    ));
+    assert_eq!(err.kind(), ErrorKind::Local);
+    assert!(mail.inboxes().is_empty());
+    assert!(registrar.verified().is_empty());
+}
+
+fn seeded_generator(
+    mail: &FakeMailProvider,
+    registrar: &FakeRegistrar,
+    seed: u64,
+) -> AccountGenerator<FakeMailProvider, FakeRegistrar> {
+    AccountGenerator::builder()
+        .poll_interval(Duration::from_millis(10))
+        .rng_seed(seed)
+        .build_with(mail.clone(), registrar.clone())
+}
+
+#[tokio::test]
+async fn seeded_runs_are_reproducible() {
+    let mut runs = Vec::new();
+    for _ in 0..2 {
+        let mail = FakeMailProvider::new();
+        let registrar = FakeRegistrar::new(mail.clone());
+        let generator = seeded_generator(&mail, &registrar, 42);
+        let first = generator.generate("S3cure-Password!").await.unwrap();
+        let second = generator.generate("S3cure-Password!").await.unwrap();
+        runs.push([(first.email, first.name), (second.email, second.name)]);
+    }
+
+    assert_eq!(runs[0], runs[1]);
+    assert_ne!(runs[0][0], runs[0][1]);
+}
+
+#[tokio::test]
+async fn aliases_fit_guerrillamail_constraints() {
+    let mail = FakeMailProvider::new();
+    let registrar = FakeRegistrar::silent();
+    for seed in 0..200 {
+        let pending = seeded_generator(&mail, &registrar, seed)
+            .start_registration("S3cure-Password!", None)
+            .await
+            .unwrap();
+
+        let (alias, domain) = pending.email.split_once('@').unwrap();
+        assert_eq!(domain, FakeMailProvider::DOMAIN);
+        // Aliases are used as GuerrillaMail usernames: short, lowercase letters and digits only
+        assert!((8..=32).contains(&alias.len()), "alias {:?}", alias);
+        assert!(
+            alias
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit()),
+            "alias {:?}",
+            alias
+        );
+        let (word, number) = alias.split_at(alias.len() - 4);
+        assert!(word.bytes().all(|b| b.is_ascii_lowercase()));
+        assert!((1000..10000).contains(&number.parse::<u32>().unwrap()));
+
+        let (first, last) = pending.name.split_once(' ').unwrap();
+        assert!(
+            !first.is_empty() && !last.is_empty(),
+            "name {:?}",
+            pending.name
+        );
+    }
+}
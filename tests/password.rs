@@ -0,0 +1,121 @@
+use meganz_account_generator::{
+    ExposeSecret, PasswordGenerator, PasswordPolicy, PasswordWeakness, estimate_entropy,
+};
+use std::collections::HashSet;
+
+fn temp_list_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!(
+        "meganz-breached-{}-{}.txt",
+        name,
+        std::process::id()
+    ))
+}
+
+#[test]
+fn default_policy_rejects_short_and_predictable_passwords() {
+    let policy = PasswordPolicy::default();
+
+    assert_eq!(
+        policy.check(""),
+        Err(PasswordWeakness::TooShort {
+            length: 0,
+            min_length: PasswordPolicy::DEFAULT_MIN_LENGTH,
+        })
+    );
+    assert!(matches!(
+        policy.check("aaaaaaaaaaaa"),
+        Err(PasswordWeakness::LowEntropy { .. })
+    ));
+    assert_eq!(policy.check("S3cure-Password!"), Ok(()));
+    assert_eq!(PasswordPolicy::permissive().check(""), Ok(()));
+}
+
+#[test]
+fn entropy_grows_with_length_and_character_classes() {
+    assert_eq!(estimate_entropy(""), 0.0);
+    assert!(estimate_entropy("abcdefgh") < estimate_entropy("abcdefghij"));
+    assert!(estimate_entropy("abcdefgh") < estimate_entropy("abcDEF12"));
+    // Repeating a character adds less than a new one
+    assert!(estimate_entropy("abcdefgg") < estimate_entropy("abcdefgh"));
+}
+
+#[test]
+fn rejects_passwords_from_breached_list_file() {
+    let path = temp_list_path("list");
+    std::fs::write(&path, "123456\nCorrect-Horse-Battery-1\r\n\n").unwrap();
+
+    let policy = PasswordPolicy::new()
+        .min_length(4)
+        .breached_passwords_file(&path)
+        .unwrap()
+        .breached_passwords(["hunter2hunter2"]);
+
+    assert_eq!(
+        policy.check("Correct-Horse-Battery-1"),
+        Err(PasswordWeakness::Breached)
+    );
+    assert_eq!(
+        policy.check("hunter2hunter2"),
+        Err(PasswordWeakness::Breached)
+    );
+    assert_eq!(policy.check("Correct-Horse-Battery-2"), Ok(()));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn generates_unique_passwords_from_alphabet() {
+    let generator = PasswordGenerator::new().length(32).alphabet("abcdef0123");
+
+    let passwords: HashSet<String> = (0..50)
+        .map(|_| generator.generate().expose_secret().to_string())
+        .collect();
+
+    assert_eq!(passwords.len(), 50);
+    for password in &passwords {
+        assert_eq!(password.chars().count(), 32);
+        assert!(password.chars().all(|c| "abcdef0123".contains(c)));
+    }
+    // Default generated passwords satisfy the default policy
+    let password = PasswordGenerator::new().generate();
+    assert_eq!(
+        PasswordPolicy::default().check(password.expose_secret()),
+        Ok(())
+    );
+}
+
+#[test]
+fn default_alphabet_needs_no_shell_quoting() {
+    // Characters bash treats specially unquoted, including `!` for history expansion
+    for c in "!$`\\\"'&|;<>()[]{}*?~# ".chars() {
+        assert!(
+            !PasswordGenerator::DEFAULT_ALPHABET.contains(c),
+            "default alphabet contains {c:?}"
+        );
+    }
+}
+
+#[test]
+fn checks_generator_configuration_against_policy() {
+    let policy = PasswordPolicy::default();
+
+    assert_eq!(policy.check_generator(&PasswordGenerator::new()), Ok(()));
+    assert_eq!(
+        policy.check_generator(&PasswordGenerator::new().length(6)),
+        Err(PasswordWeakness::TooShort {
+            length: 6,
+            min_length: PasswordPolicy::DEFAULT_MIN_LENGTH,
+        })
+    );
+    // 32 characters from a two-letter alphabet carry exactly 32 bits
+    assert_eq!(
+        policy.check_generator(&PasswordGenerator::new().length(32).alphabet("ab")),
+        Err(PasswordWeakness::LowEntropy {
+            bits: 32.0,
+            min_bits: PasswordPolicy::DEFAULT_MIN_ENTROPY_BITS,
+        })
+    );
+    assert_eq!(
+        policy.check_generator(&PasswordGenerator::new().length(40).alphabet("ab")),
+        Ok(())
+    );
+}
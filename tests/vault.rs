@@ -0,0 +1,215 @@
+use meganz_account_generator::vault::{VAULT_VERSION, Vault, VaultEntry, VaultError};
+use meganz_account_generator::{Error, ExposeSecret, SecretString};
+use std::path::PathBuf;
+
+fn temp_vault_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "meganz-vault-{}-{}.vault",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn entry(email: &str) -> VaultEntry {
+    VaultEntry {
+        email: email.to_string(),
+        password: SecretString::from("S3cure-Password!"),
+        name: "Test User".to_string(),
+        recovery_key: None,
+    }
+}
+
+#[test]
+fn appends_and_reads_entries_after_reopening() {
+    let path = temp_vault_path("roundtrip");
+    let passphrase = SecretString::from("correct horse battery staple");
+
+    let mut vault = Vault::create(&path, &passphrase).unwrap();
+    vault.append(&entry("one@example.com")).unwrap();
+    drop(vault);
+
+    let mut vault = Vault::open_or_create(&path, &passphrase).unwrap();
+    vault.append(&entry("two@example.com")).unwrap();
+
+    let entries = Vault::open(&path, &passphrase).unwrap().entries().unwrap();
+    let emails: Vec<_> = entries.iter().map(|e| e.email.as_str()).collect();
+    assert_eq!(emails, ["one@example.com", "two@example.com"]);
+    assert_eq!(entries[0].password.expose_secret(), "S3cure-Password!");
+
+    let raw = std::fs::read(&path).unwrap();
+    assert!(!raw.windows(6).any(|w| w == b"S3cure"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn rewrite_replaces_entries_under_same_passphrase() {
+    let path = temp_vault_path("rewrite");
+    let passphrase = SecretString::from("passphrase");
+    let mut vault = Vault::create(&path, &passphrase).unwrap();
+    vault.append(&entry("old@example.com")).unwrap();
+    vault.append(&entry("other@example.com")).unwrap();
+
+    vault
+        .rewrite(&[entry("new@example.com"), entry("other@example.com")])
+        .unwrap();
+    vault.append(&entry("third@example.com")).unwrap();
+
+    let entries = Vault::open(&path, &passphrase).unwrap().entries().unwrap();
+    let emails: Vec<_> = entries.iter().map(|e| e.email.as_str()).collect();
+    assert_eq!(
+        emails,
+        ["new@example.com", "other@example.com", "third@example.com"]
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn rejects_wrong_passphrase() {
+    let path = temp_vault_path("wrong-passphrase");
+    Vault::create(&path, &SecretString::from("right")).unwrap();
+
+    let err = Vault::open(&path, &SecretString::from("wrong")).unwrap_err();
+
+    assert!(matches!(err, Error::Vault(VaultError::WrongPassphrase)));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn detects_tampered_records() {
+    let path = temp_vault_path("tampered");
+    let passphrase = SecretString::from("passphrase");
+    let mut vault = Vault::create(&path, &passphrase).unwrap();
+    vault.append(&entry("one@example.com")).unwrap();
+
+    let mut raw = std::fs::read(&path).unwrap();
+    *raw.last_mut().unwrap() ^= 1;
+    std::fs::write(&path, raw).unwrap();
+
+    let err = vault.entries().unwrap_err();
+    assert!(matches!(err, Error::Vault(VaultError::Corrupt)));
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// Split a vault file into its header and its length-prefixed records.
+fn split_records(raw: &[u8]) -> (Vec<u8>, Vec<Vec<u8>>) {
+    // Magic, version, KDF parameters and salt, then the nonce, sealed record count and tag of `check`
+    let header_len = 8 + 1 + 12 + 16 + 24 + 8 + 16;
+    let (header, mut rest) = raw.split_at(header_len);
+    let mut records = Vec::new();
+    while !rest.is_empty() {
+        let len = 4 + 24 + u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+        let (record, tail) = rest.split_at(len);
+        records.push(record.to_vec());
+        rest = tail;
+    }
+    (header.to_vec(), records)
+}
+
+#[test]
+fn detects_reordered_duplicated_and_dropped_records() {
+    let path = temp_vault_path("reordered");
+    let passphrase = SecretString::from("passphrase");
+    let mut vault = Vault::create(&path, &passphrase).unwrap();
+    for email in ["one@example.com", "two@example.com", "six@example.com"] {
+        vault.append(&entry(email)).unwrap();
+    }
+    let (header, records) = split_records(&std::fs::read(&path).unwrap());
+    assert_eq!(records.len(), 3);
+
+    let tampered = [
+        ("reordered", vec![1, 0, 2]),
+        ("duplicated", vec![0, 0, 1, 2]),
+        ("dropped from the middle", vec![0, 2]),
+        ("dropped from the end", vec![0, 1]),
+    ];
+    for (what, order) in tampered {
+        let mut raw = header.clone();
+        for i in order {
+            raw.extend_from_slice(&records[i]);
+        }
+        std::fs::write(&path, raw).unwrap();
+
+        let err = vault.entries().unwrap_err();
+        assert!(
+            matches!(err, Error::Vault(VaultError::Corrupt)),
+            "{what}: {err:?}"
+        );
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn rejects_excessive_kdf_parameters() {
+    let path = temp_vault_path("kdf-params");
+    let passphrase = SecretString::from("passphrase");
+    Vault::create(&path, &passphrase).unwrap();
+    let original = std::fs::read(&path).unwrap();
+
+    // t_cost and p_cost follow the magic, version and m_cost
+    for offset in [13, 17] {
+        let mut raw = original.clone();
+        raw[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&path, raw).unwrap();
+
+        let err = Vault::open(&path, &passphrase).unwrap_err();
+        assert!(matches!(err, Error::Vault(VaultError::Corrupt)), "{err:?}");
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn reads_and_upgrades_version_1_vault() {
+    let path = temp_vault_path("v1");
+    std::fs::copy(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/vault/v1.vault"),
+        &path,
+    )
+    .unwrap();
+    let passphrase = SecretString::from("passphrase");
+
+    let mut vault = Vault::open(&path, &passphrase).unwrap();
+    let emails: Vec<_> = vault
+        .entries()
+        .unwrap()
+        .into_iter()
+        .map(|e| e.email)
+        .collect();
+    assert_eq!(emails, ["one@example.com", "two@example.com"]);
+
+    vault.append(&entry("three@example.com")).unwrap();
+
+    assert_eq!(std::fs::read(&path).unwrap()[8], VAULT_VERSION);
+    let entries = Vault::open(&path, &passphrase).unwrap().entries().unwrap();
+    let emails: Vec<_> = entries.iter().map(|e| e.email.as_str()).collect();
+    assert_eq!(
+        emails,
+        ["one@example.com", "two@example.com", "three@example.com"]
+    );
+    assert_eq!(entries[0].password.expose_secret(), "S3cure-Password!");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn stores_recovery_key_when_present() {
+    let path = temp_vault_path("recovery-key");
+    let passphrase = SecretString::from("correct horse battery staple");
+
+    let mut vault = Vault::create(&path, &passphrase).unwrap();
+    vault.append(&entry("plain@example.com")).unwrap();
+    vault
+        .append(&VaultEntry {
+            recovery_key: Some(SecretString::from("UmVjb3Zlcnkta2V5")),
+            ..entry("recoverable@example.com")
+        })
+        .unwrap();
+
+    let entries = vault.entries().unwrap();
+    assert!(entries[0].recovery_key.is_none());
+    assert_eq!(
+        entries[1].recovery_key.as_ref().unwrap().expose_secret(),
+        "UmVjb3Zlcnkta2V5"
+    );
+    std::fs::remove_file(&path).unwrap();
+}